@@ -1,149 +1,875 @@
 use std::str::FromStr;
 use crate::chunk_type::ChunkType;
+use crate::cipher::Cipher;
 use crate::chunk::Chunk;
-use crate::png::Png;
-use optional_field::Field;
-use std::fs::File;
+use crate::padding::Padding;
+use crate::png::{Placement, Png};
+use crate::envelope::{Envelope, Secret, Stanza};
+use crate::keyfile::KeyFile;
+use crate::keystore::Keystore;
+use crate::listing;
+use crate::attachment::Attachment;
+use crate::batch;
+use crate::deniable;
+use crate::extract;
+use crate::shamir::{self, Share, Split};
+use crate::secret_input;
+use crate::recipient::Recipient;
+use crate::signature::{Signer, Verifier};
+use crate::commands::{BatchCommand, Command, KeyCommand, SecretOptions};
+use zeroize::Zeroizing;
+use std::collections::HashSet;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
 use std::io::{self, Read, Write};
 use generic_array::GenericArray;
 use generic_array::typenum::U32;
 use deoxys::{
-    aead::{Aead, KeyInit},
+    aead::{KeyInit, OsRng},
     DeoxysII256,
 };
 
-pub enum Args {
-    Encode {
-        file_path: String,
-        chunk_type: ChunkType,
-        message: String,
-        output_file: Field<String>,
-    },
-    Decode {
-        file_path: String,
-        chunk_type: ChunkType,
-    },
-    Remove {
-        file_path: String,
-        chunk_type: ChunkType,
-    },
-    Print {
-        file_path: String,
-    },
-}
+impl Command {
+    pub fn read_file(file_path: String) -> Result<Png, Box<dyn std::error::Error>> {
+        let buffer = Self::read_bytes(&file_path)?;
+        let png = Png::try_from(&buffer[..])?;
+        Ok(png)
+    }
 
-impl Args {
-    pub fn new(command: &str, args: &[String]) -> Self {
-        match command {
-            "encode" => {
-                Self::Encode {
-                    file_path: args[0].clone(),
-                    chunk_type: ChunkType::from_str(&args[1]).unwrap(),
-                    message: args[2].clone(),
-                    output_file: {
-                        if args.len() > 3 {
-                            Field::Present(Some(args[3].clone()))
-                        } else {
-                            Field::Missing
-                        }
-                    },
-                }
-            },
-            "decode" => {
-                Self::Decode {
-                    file_path: args[0].clone(),
-                    chunk_type: ChunkType::from_str(&args[1]).unwrap(),
-                }
-            },
-            "remove" => {
-                Self::Remove {
-                    file_path: args[0].clone(),
-                    chunk_type: ChunkType::from_str(&args[1]).unwrap(),
-                }
-            },
-            "print" => {
-                Self::Print {
-                    file_path: args[0].clone(),
-                }
-            },
-            _ => panic!("Invalid command"),
-        }
+    fn read_bytes(path: &str) -> io::Result<Vec<u8>> {
+        Self::read_bytes_from(path, io::stdin())
     }
 
-    pub fn read_file(file_path: String) -> Result<Png, Box<dyn std::error::Error>> {
-        let mut file = File::open(file_path)?;
+    fn read_bytes_from<R: Read>(path: &str, mut stdin: R) -> io::Result<Vec<u8>> {
         let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
-        let png = Png::try_from(&buffer[..]).unwrap();
-        Ok(png)
+        if path == "-" {
+            stdin.read_to_end(&mut buffer)?;
+        } else {
+            File::open(path)?.read_to_end(&mut buffer)?;
+        }
+        Ok(buffer)
+    }
+
+    fn write_bytes(path: &str, bytes: &[u8]) -> io::Result<()> {
+        Self::write_bytes_to(path, bytes, io::stdout())
+    }
+
+    fn write_bytes_to<W: Write>(path: &str, bytes: &[u8], mut stdout: W) -> io::Result<()> {
+        if path == "-" {
+            stdout.write_all(bytes)?;
+            return stdout.flush();
+        }
+        File::create(path)?.write_all(bytes)
     }
 
     pub fn execute(&self) -> Result<(), Box<dyn std::error::Error>> {
         match self {
-            Self::Encode { file_path, chunk_type, message, output_file }  => {
-                let mut png = Self::read_file(file_path.clone())?;
-                png.append_chunk(Chunk::new(chunk_type.clone(), message.bytes().collect()));
-                let output = png.as_bytes();
-                if output_file.clone().is_present() {
-                    let mut file = File::create(output_file.clone().unwrap())?;
-                    file.write_all(&output)?;
+            Self::Encode { file, chunk_type, message, output_file, message_file, attachment, output, secrets, placement, cipher, padding, recipients, sign, bind, decoy, decoy_passphrase_file, deniable, split, images, encrypt, no_encrypt, yes }  => {
+                let mut png = Self::read_file(file.clone())?;
+                if png.chunk_by_type(&chunk_type.to_string()).is_some() {
+                    return Err(format!("{file} already has a {chunk_type} chunk").into());
+                }
+                let message = match (message, message_file, attachment) {
+                    (Some(message), _, _) => message.as_bytes().to_vec(),
+                    (_, Some(path), _) if path == "-" && file == "-" => {
+                        return Err("The image and the message cannot both come from stdin".into());
+                    },
+                    (_, Some(path), _) => Self::read_bytes(path).map_err(|e| format!("Cannot read message file {path}: {e}"))?,
+                    (_, _, Some(path)) => Attachment::read(path)?.as_bytes(),
+                    _ => return Err("No message given".into()),
+                };
+                let data = if decoy.is_some() || *deniable {
+                    Self::seal_deniable(&message, secrets, decoy.as_deref(), decoy_passphrase_file.as_deref())?
+                } else if *no_encrypt {
+                    message.clone()
+                } else if let Some(secrets) = Self::encryption_secrets(recipients, secrets, *encrypt || *yes)? {
+                    let signer = Self::load_signer(sign.as_deref())?;
+                    let carrier = bind.then(|| png.carrier_binding(chunk_type));
+                    Envelope::seal(
+                        &message,
+                        &secrets,
+                        cipher.unwrap_or(Cipher::DeoxysII256),
+                        padding.unwrap_or(Padding::Padme),
+                        signer.as_ref().map(|signer| (signer, chunk_type)),
+                        carrier.as_deref(),
+                    )?.to_chunk_data()
+                } else if sign.is_some() {
+                    return Err("Only encrypted payloads can be signed".into());
+                } else if *bind {
+                    return Err("Only encrypted payloads can be bound to their carrier".into());
                 } else {
-                    assert!(png.chunk_by_type(&String::from_utf8(chunk_type.bytes().to_vec()).unwrap()).is_some(), "Chunk already exists");
-                    let mut file = File::create(file_path)?;
-                    file.write_all(&output)?;
+                    message.clone()
+                };
+                if let Some(split) = split {
+                    return Self::embed_shares(&data, chunk_type, *split, *placement, file, images);
                 }
+                png.insert_chunk(Chunk::new(chunk_type.clone(), data), *placement)?;
+                Self::write_file(&png, output_file.as_deref().or(output.output.as_deref()), file)?;
             },
-            Self::Decode { file_path, chunk_type } => {
-                let png = Self::read_file(file_path.clone())?;
-                let Some(chunk) = png.chunk_by_type(&String::from_utf8(chunk_type.bytes().to_vec()).unwrap()) else {
-                    panic!("Chunk not found");
+            Self::Decode { file, chunk_type, secrets, signer, images, output, output_dir, nonce, no_encrypt, yes } => {
+                let png = Self::read_file(file.clone())?;
+                let Some(chunk) = png.chunk_by_type(&chunk_type.to_string()) else {
+                    return Err("Chunk not found".into());
+                };
+                let combined;
+                let chunk = if Share::matches(chunk.data()) {
+                    combined = Chunk::new(chunk_type.clone(), Self::combine_shares(chunk.data(), chunk_type, images)?);
+                    &combined
+                } else {
+                    chunk
                 };
-                let data = chunk.data_as_string().unwrap();
-                print!("Decrypt? [Y/n] ");
-                io::stdout().flush()?;
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-                match input.trim() {
-                    "Y" | "y" | "" => {
-                        print!("Enter key: ");
-                        io::stdout().flush()?;
-                        let mut input = String::new();
-                        io::stdin().read_line(&mut input)?;
-                        let key: GenericArray<u8, U32> = *GenericArray::from_slice(&hex::decode(input.trim()).unwrap());
-                        print!("Enter nonce: ");
-                        io::stdout().flush()?;
-                        let mut input = String::new();
-                        io::stdin().read_line(&mut input)?;
-                        let nonce: [u8; 15] = Self::convert_to_fixed_slice(hex::decode(input.trim()).unwrap().as_slice());
-                        let nonce: GenericArray<u8, _> =  *GenericArray::from_slice(&nonce);
-                        let cipher = DeoxysII256::new(&key);
-                        let plaintext = cipher.decrypt(&nonce, hex::decode(data).unwrap().as_ref()).unwrap();
-                        println!("Retrieved message: {}", String::from_utf8(plaintext).unwrap());
+                if let Some(signer) = signer {
+                    let Some(envelope) = Envelope::from_chunk_data(chunk.data())? else {
+                        return Err("Payload is not signed".into());
+                    };
+                    envelope.verify(&Verifier::from_str(signer)?, chunk_type)?;
+                    eprintln!("Good signature from {signer}");
+                }
+                let message = Zeroizing::new(match !*no_encrypt && secret_input::confirm("Decrypt? [Y/n] ", *yes)? {
+                    true => {
+                        match Envelope::from_chunk_data(chunk.data())? {
+                            Some(envelope) => {
+                                if signer.is_none() && envelope.signature.is_some() {
+                                    eprintln!("Payload is signed, pass --signer to verify it");
+                                }
+                                envelope.open(&Self::envelope_secrets(&envelope, secrets)?, &Self::carrier(&png, &envelope, chunk_type))?
+                            },
+                            None => match chunk.data_as_string() {
+                                Ok(data) if hex::decode(&data).is_ok() => {
+                                    Self::decrypt_legacy(&data, secrets, nonce.as_deref())?
+                                },
+                                _ if !deniable::matches(chunk.data()) => {
+                                    return Err("Payload is not encrypted, decode it with --no-encrypt".into());
+                                },
+                                _ => {
+                                    let passphrase = match secret_input::passphrase(secrets.passphrase_file.as_deref())? {
+                                        Some(passphrase) => passphrase,
+                                        None => secret_input::prompt_hidden("Enter passphrase: ")?,
+                                    };
+                                    deniable::open(chunk.data(), &passphrase)?
+                                },
+                            },
+                        }
                     },
-                    "N" | "n" => println!("Retrieved message: {}", &chunk.data_as_string().unwrap()),
-                    _ => panic!("Invalid input"),
+                    false => chunk.data().to_vec(),
+                });
+                if Attachment::matches(&message) {
+                    let attachment = Attachment::try_from(message.as_slice())?;
+                    match (output_dir, &output.output) {
+                        (Some(dir), _) => {
+                            let path = Self::restore(Path::new(dir), &attachment)?;
+                            eprintln!("Restored {} ({}, {} bytes)", path.display(), attachment.mime, attachment.data.len());
+                        },
+                        (_, Some(path)) => Self::write_bytes(path, &attachment.data)?,
+                        _ => return Err(format!("Payload is the file {} ({}), restore it with --output-dir", attachment.name, attachment.mime).into()),
+                    };
+                    return Ok(());
+                }
+                match (output_dir, &output.output) {
+                    (Some(_), _) => return Err("Payload is a message, not a file, save it with --output".into()),
+                    (_, Some(path)) => Self::write_bytes(path, &message)?,
+                    _ => println!("Retrieved message: {}", std::str::from_utf8(&message).map_err(|_| "Message is not valid UTF-8, save it with --output")?),
                 };
             },
-            Self::Remove { file_path, chunk_type } => {
-                let mut png = Self::read_file(file_path.clone())?;
-                let chunk = String::from_utf8(chunk_type.bytes().to_vec()).unwrap();
-                png.remove_chunk(&chunk)?;
-                let output = png.as_bytes();
-                let mut file = File::create(file_path)?;
-                file.write_all(&output)?;
-                println!("Removed chunk: {chunk}");
-            },
-            Self::Print { file_path } => {
-                let png = Self::read_file(file_path.clone())?;
+            Self::Remove { file, chunk_type, output } => {
+                let mut png = Self::read_file(file.clone())?;
+                png.remove_chunk(&chunk_type.to_string())?;
+                Self::write_file(&png, output.output.as_deref(), file)?;
+                eprintln!("Removed chunk: {chunk_type}");
+            },
+            Self::Rekey { file, chunk_type, output, secrets, new_key_files, new_passphrase_file, recipients, cipher, sign } => {
+                let mut png = Self::read_file(file.clone())?;
+                let signer = Self::load_signer(sign.as_deref())?;
+                Self::rekey_chunk(
+                    &mut png,
+                    chunk_type,
+                    |envelope| Self::envelope_secrets(envelope, secrets),
+                    || {
+                        let mut secrets = new_key_files
+                            .iter()
+                            .map(|path| Ok(KeyFile::read(path)?.secret()?))
+                            .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+                        if let Some(path) = new_passphrase_file {
+                            secrets.extend(secret_input::passphrase(Some(path))?.map(|passphrase| Secret::Passphrase(passphrase.to_string())));
+                        }
+                        for recipient in recipients {
+                            secrets.push(Secret::Recipient(Recipient::from_str(recipient)?));
+                        }
+                        if secrets.is_empty() {
+                            secrets.push(Self::passphrase_or_random_key("New passphrase (leave empty for a random key): ")?);
+                        }
+                        Ok(secrets)
+                    },
+                    *cipher,
+                    signer.as_ref(),
+                )?;
+                Self::write_file(&png, output.output.as_deref(), file)?;
+                eprintln!("Rekeyed chunk: {chunk_type}");
+            },
+            Self::Print { file } => {
+                let png = Self::read_file(file.clone())?;
                 println!("{png:?}");
             },
+            Self::List { file, format } => {
+                let png = Self::read_file(file.clone())?;
+                println!("{}", listing::render(&listing::entries(&png), *format)?.trim_end());
+            },
+            Self::ExtractAll { file, output_dir } => {
+                let png = Self::read_file(file.clone())?;
+                let dir = Path::new(output_dir);
+                let extracted = extract::extract_all(&png, dir)?;
+                for entry in &extracted {
+                    let notes: Vec<&str> = [
+                        entry.utf8.then_some("utf-8"),
+                        entry.zlib.then_some("zlib"),
+                        entry.payload,
+                        (!entry.crc_valid).then_some("bad crc"),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                    eprintln!("{} {} bytes -> {} {}", entry.chunk_type, entry.length, entry.file, notes.join(", "));
+                }
+                eprintln!("Extracted {} chunks, manifest at {}", extracted.len(), dir.join(extract::MANIFEST).display());
+            },
+            Self::Verify { file, chunk_type, signer } => {
+                let png = Self::read_file(file.clone())?;
+                let Some(chunk) = png.chunk_by_type(&chunk_type.to_string()) else {
+                    return Err("Chunk not found".into());
+                };
+                let Some(envelope) = Envelope::from_chunk_data(chunk.data())? else {
+                    return Err("Chunk does not hold an envelope".into());
+                };
+                envelope.verify(&Verifier::from_str(signer)?, chunk_type)?;
+                println!("Good signature from {signer}");
+            },
+            Self::Keygen { kind, output } => {
+                let key = KeyFile::generate(kind)?;
+                match output {
+                    Some(path) => {
+                        key.write(path).map_err(|e| format!("Cannot write key file {path}: {e}"))?;
+                        println!("Wrote key to {path}");
+                        if let Some(public_key) = key.public_key() {
+                            println!("Public key: {public_key}");
+                        }
+                    },
+                    None => println!("{key}"),
+                };
+            },
+            Self::Key(KeyCommand::Add { name, kind, key_file }) => {
+                let path = Keystore::default_path()?;
+                let (mut keystore, passphrase) = if path.exists() {
+                    let passphrase = Keystore::passphrase(false)?;
+                    (Keystore::open(&path, &passphrase)?, passphrase)
+                } else {
+                    println!("Creating keystore at {}", path.display());
+                    (Keystore::default(), Keystore::passphrase(true)?)
+                };
+                let key = match key_file {
+                    Some(key_file) => KeyFile::read(key_file)?,
+                    None => KeyFile::generate(kind)?,
+                };
+                let public_key = key.public_key();
+                keystore.add(name, key)?;
+                keystore.save(&path, &passphrase)?;
+                println!("Added key {name}");
+                if let Some(public_key) = public_key {
+                    println!("Public key: {public_key}");
+                }
+            },
+            Self::Key(KeyCommand::List) => {
+                let keystore = Keystore::open(&Keystore::default_path()?, &Keystore::passphrase(false)?)?;
+                for (name, key) in keystore.entries() {
+                    let kind = match key {
+                        KeyFile::Symmetric(_) => "symmetric",
+                        KeyFile::Identity(_) => "x25519",
+                        KeyFile::Signing(_) => "ed25519",
+                    };
+                    println!("{name}\t{kind}\t{}", key.public_key().unwrap_or_default());
+                }
+            },
+            Self::Key(KeyCommand::Remove { name }) => {
+                let path = Keystore::default_path()?;
+                let passphrase = Keystore::passphrase(false)?;
+                let mut keystore = Keystore::open(&path, &passphrase)?;
+                keystore.remove(name)?;
+                keystore.save(&path, &passphrase)?;
+                println!("Removed key {name}");
+            },
+            Self::Key(KeyCommand::Export { name, output }) => {
+                let keystore = Keystore::open(&Keystore::default_path()?, &Keystore::passphrase(false)?)?;
+                let Some(key) = keystore.get(name) else {
+                    return Err(format!("No key named {name} in the keystore").into());
+                };
+                match output {
+                    Some(path) => {
+                        key.write(path).map_err(|e| format!("Cannot write key file {path}: {e}"))?;
+                        println!("Wrote key to {path}");
+                    },
+                    None => println!("{key}"),
+                };
+            },
+            Self::Batch(BatchCommand::Encode { chunk_type, batch, output_dir, message, message_file, secrets, placement, cipher, padding, recipients, sign, bind, no_encrypt }) => {
+                let message = match (message, message_file) {
+                    (Some(message), _) => message.as_bytes().to_vec(),
+                    (_, Some(path)) => Self::read_bytes(path).map_err(|e| format!("Cannot read message file {path}: {e}"))?,
+                    _ => return Err("No message given".into()),
+                };
+                let inputs = batch::collect_inputs(&batch.inputs)?;
+                let secrets = match no_encrypt {
+                    true => None,
+                    false => Self::encryption_secrets(recipients, secrets, true)?,
+                };
+                let signer = Self::load_signer(sign.as_deref())?;
+                let outcomes = batch::run(&inputs, Some(Path::new(output_dir)), batch.jobs, |input, output| {
+                    let mut png = Self::read_intact(input)?;
+                    if png.chunk_by_type(&chunk_type.to_string()).is_some() {
+                        return Err(format!("Already has a {chunk_type} chunk").into());
+                    }
+                    let data = match &secrets {
+                        Some(secrets) => Envelope::seal(
+                            &message,
+                            secrets,
+                            cipher.unwrap_or(Cipher::DeoxysII256),
+                            padding.unwrap_or(Padding::Padme),
+                            signer.as_ref().map(|signer| (signer, chunk_type)),
+                            bind.then(|| png.carrier_binding(chunk_type)).as_deref(),
+                        )?.to_chunk_data(),
+                        None => message.clone(),
+                    };
+                    png.insert_chunk(Chunk::new(chunk_type.clone(), data), *placement)?;
+                    fs::write(output.unwrap(), png.as_bytes())?;
+                    Ok(format!("Encoded {chunk_type}"))
+                })?;
+                batch::summarize(&outcomes, batch.summary.as_deref())?;
+            },
+            Self::Batch(BatchCommand::Decode { chunk_type, batch, output_dir, secrets, signer }) => {
+                let inputs = batch::collect_inputs(&batch.inputs)?;
+                let secrets = secrets.load()?;
+                let verifier = signer.as_deref().map(Verifier::from_str).transpose()?;
+                let output_dir = output_dir.as_deref().map(Path::new);
+                let outcomes = batch::run(&inputs, output_dir, batch.jobs, |input, output| {
+                    let png = Png::try_from(fs::read(input)?.as_slice())?;
+                    let Some(chunk) = png.chunk_by_type(&chunk_type.to_string()) else {
+                        return Err("Chunk not found".into());
+                    };
+                    if Share::matches(chunk.data()) {
+                        return Err("Split payloads cannot be decoded in a batch".into());
+                    }
+                    let message = Zeroizing::new(match Envelope::from_chunk_data(chunk.data())? {
+                        Some(envelope) => {
+                            if let Some(verifier) = &verifier {
+                                envelope.verify(verifier, chunk_type)?;
+                            }
+                            if secrets.is_empty() {
+                                return Err("Payload is encrypted, supply a key or passphrase".into());
+                            }
+                            envelope.open(&secrets, &Self::carrier(&png, &envelope, chunk_type))?
+                        },
+                        None if verifier.is_some() => return Err("Payload is not signed".into()),
+                        None => chunk.data().to_vec(),
+                    });
+                    if Attachment::matches(&message) {
+                        let attachment = Attachment::try_from(message.as_slice())?;
+                        let Some(dir) = output.map(|output| output.with_extension("")) else {
+                            return Err(format!("Payload is the file {} ({}), restore it with --output-dir", attachment.name, attachment.mime).into());
+                        };
+                        fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {e}", dir.display()))?;
+                        let path = Self::restore(&dir, &attachment)?;
+                        return Ok(format!("Restored {} ({}, {} bytes)", path.display(), attachment.mime, attachment.data.len()));
+                    }
+                    match output {
+                        Some(output) => {
+                            let path = output.with_extension(if std::str::from_utf8(&message).is_ok() { "txt" } else { "bin" });
+                            fs::write(&path, &message)?;
+                            Ok(format!("Wrote {}", path.display()))
+                        },
+                        None => Ok(String::from_utf8_lossy(&message).into_owned()),
+                    }
+                })?;
+                if output_dir.is_none() {
+                    for outcome in outcomes.iter().filter(|outcome| outcome.ok) {
+                        println!("{}: {}", outcome.input, outcome.message);
+                    }
+                }
+                batch::summarize(&outcomes, batch.summary.as_deref())?;
+            },
+            Self::Batch(BatchCommand::Remove { chunk_type, batch, output_dir }) => {
+                let inputs = batch::collect_inputs(&batch.inputs)?;
+                let outcomes = batch::run(&inputs, Some(Path::new(output_dir)), batch.jobs, |input, output| {
+                    let mut png = Self::read_intact(input)?;
+                    png.remove_chunk(&chunk_type.to_string())?;
+                    fs::write(output.unwrap(), png.as_bytes())?;
+                    Ok(format!("Removed {chunk_type}"))
+                })?;
+                batch::summarize(&outcomes, batch.summary.as_deref())?;
+            },
+            Self::Batch(BatchCommand::Validate { batch, chunk_type, signer }) => {
+                let inputs = batch::collect_inputs(&batch.inputs)?;
+                let verifier = signer.as_deref().map(Verifier::from_str).transpose()?;
+                let outcomes = batch::run(&inputs, None, batch.jobs, |input, _| {
+                    let png = Png::try_from(fs::read(input)?.as_slice())?;
+                    Self::validate(&png)?;
+                    if let Some(chunk_type) = chunk_type {
+                        let Some(chunk) = png.chunk_by_type(&chunk_type.to_string()) else {
+                            return Err(format!("No {chunk_type} chunk").into());
+                        };
+                        if let Some(verifier) = &verifier {
+                            let Some(envelope) = Envelope::from_chunk_data(chunk.data())? else {
+                                return Err("Chunk does not hold an envelope".into());
+                            };
+                            envelope.verify(verifier, chunk_type)?;
+                        }
+                    }
+                    Ok(format!("{} chunks", png.chunks().len()))
+                })?;
+                batch::summarize(&outcomes, batch.summary.as_deref())?;
+            },
+        };
+        Ok(())
+    }
+
+    fn restore(dir: &Path, attachment: &Attachment) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+        let path = dir.join(&attachment.name);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| format!("Cannot create {}: {e}", path.display()))?;
+        file.write_all(&attachment.data)?;
+        Ok(path)
+    }
+
+    fn read_intact(path: &Path) -> Result<Png, Box<dyn std::error::Error>> {
+        let png = Png::try_from(fs::read(path)?.as_slice())?;
+        Self::validate(&png)?;
+        Ok(png)
+    }
+
+    fn validate(png: &Png) -> Result<(), Box<dyn std::error::Error>> {
+        let entries = listing::entries(png);
+        if let Some(entry) = entries.iter().find(|entry| !entry.crc_valid) {
+            return Err(format!("{} chunk at offset {} has a bad CRC", entry.chunk_type, entry.offset).into());
+        }
+        if entries.first().map(|entry| entry.chunk_type.as_str()) != Some("IHDR") {
+            return Err("First chunk is not IHDR".into());
+        }
+        if entries.last().map(|entry| entry.chunk_type.as_str()) != Some("IEND") {
+            return Err("Last chunk is not IEND".into());
+        }
+        let idats: Vec<usize> = entries.iter().filter(|entry| entry.chunk_type == "IDAT").map(|entry| entry.index).collect();
+        let (Some(first), Some(last)) = (idats.first(), idats.last()) else {
+            return Err("No IDAT chunk".into());
+        };
+        if last - first + 1 != idats.len() {
+            return Err("IDAT chunks are not consecutive".into());
+        }
+        Ok(())
+    }
+
+    fn envelope_secrets(envelope: &Envelope, sources: &SecretOptions) -> Result<Vec<Secret>, Box<dyn std::error::Error>> {
+        let has_key = envelope.has_stanza(Stanza::KEY);
+        let has_passphrase = envelope.has_stanza(Stanza::PASSPHRASE);
+        let supplied = sources.load()?;
+        if !supplied.is_empty() {
+            Ok(supplied)
+        } else if has_key && has_passphrase {
+            let input = secret_input::prompt_hidden("Enter key or passphrase: ")?;
+            let passphrase = Secret::Passphrase(input.to_string());
+            match secret_input::parse_key(&input) {
+                Ok(key) => Ok(vec![Secret::Key(key), passphrase]),
+                Err(_) => Ok(vec![passphrase]),
+            }
+        } else if has_passphrase {
+            Ok(vec![Secret::Passphrase(secret_input::prompt_hidden("Enter passphrase: ")?.to_string())])
+        } else if has_key {
+            Ok(vec![Secret::Key(secret_input::parse_key(&secret_input::prompt_hidden("Enter key: ")?)?)])
+        } else {
+            Err("Payload is sealed to a public key, supply --identity or --key-file".into())
+        }
+    }
+
+    fn rekey_chunk<O, N>(png: &mut Png, chunk_type: &ChunkType, old_secrets: O, new_secrets: N, cipher: Option<Cipher>, signer: Option<&Signer>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        O: FnOnce(&Envelope) -> Result<Vec<Secret>, Box<dyn std::error::Error>>,
+        N: FnOnce() -> Result<Vec<Secret>, Box<dyn std::error::Error>>,
+    {
+        let Some(chunk) = png.chunk_by_type(&chunk_type.to_string()) else {
+            return Err("Chunk not found".into());
+        };
+        let Some(envelope) = Envelope::from_chunk_data(chunk.data())? else {
+            return Err("Only envelopes can be rekeyed, re-encode older payloads instead".into());
+        };
+        let carrier = Self::carrier(png, &envelope, chunk_type);
+        let plaintext = Zeroizing::new(envelope.open(&old_secrets(&envelope)?, &carrier)?);
+
+        let secrets = new_secrets()?;
+        if envelope.signature.is_some() && signer.is_none() {
+            eprintln!("The old signature no longer applies, pass --sign to sign the new payload");
+        }
+        let rekeyed = Envelope::seal(
+            &plaintext,
+            &secrets,
+            cipher.unwrap_or(envelope.cipher),
+            envelope.padding,
+            signer.map(|signer| (signer, chunk_type)),
+            envelope.is_bound().then_some(carrier.as_slice()),
+        )?;
+        png.replace_chunk(Chunk::new(chunk_type.clone(), rekeyed.to_chunk_data()))?;
+        Ok(())
+    }
+
+    fn carrier(png: &Png, envelope: &Envelope, chunk_type: &ChunkType) -> Vec<u8> {
+        if envelope.is_bound() {
+            png.carrier_binding(chunk_type)
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn load_signer(signer: Option<&str>) -> Result<Option<Signer>, Box<dyn std::error::Error>> {
+        match signer {
+            Some(path) => match KeyFile::read(path)? {
+                KeyFile::Signing(signer) => Ok(Some(signer)),
+                _ => Err("--sign needs an ed25519 key file".into()),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn encryption_secrets(recipients: &[String], sources: &SecretOptions, encrypt: bool) -> Result<Option<Vec<Secret>>, Box<dyn std::error::Error>> {
+        let mut secrets = sources.load()?;
+        let supplied = !secrets.is_empty();
+        for recipient in recipients {
+            secrets.push(Secret::Recipient(Recipient::from_str(recipient)?));
+        }
+
+        if supplied {
+            Ok(Some(secrets))
+        } else if !recipients.is_empty() {
+            if secret_input::is_interactive() {
+                let passphrase = secret_input::prompt_hidden("Also protect with a passphrase (leave empty to skip): ")?;
+                if !passphrase.is_empty() {
+                    secrets.push(Secret::Passphrase(passphrase.to_string()));
+                }
+            }
+            Ok(Some(secrets))
+        } else if secret_input::confirm("Encrypt? [Y/n] ", encrypt)? {
+            Ok(Some(vec![Self::passphrase_or_random_key("Passphrase (leave empty for a random key): ")?]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn passphrase_or_random_key(message: &str) -> Result<Secret, Box<dyn std::error::Error>> {
+        if secret_input::is_interactive() {
+            let passphrase = secret_input::prompt_hidden(message)?;
+            if !passphrase.is_empty() {
+                return Ok(Secret::Passphrase(passphrase.to_string()));
+            }
+        }
+        let key: GenericArray<u8, U32> = DeoxysII256::generate_key(&mut OsRng);
+        eprintln!("Store this safely...");
+        eprintln!("Secret key: {}", hex::encode(key));
+        Ok(Secret::Key(key))
+    }
+
+    fn seal_deniable(message: &[u8], sources: &SecretOptions, decoy: Option<&str>, decoy_passphrase_file: Option<&str>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if env::var_os(secret_input::KEY_VAR).is_some_and(|key| !key.is_empty()) {
+            return Err(format!("Deniable payloads only support passphrases, unset {}", secret_input::KEY_VAR).into());
+        }
+        let passphrase = match secret_input::passphrase(sources.passphrase_file.as_deref())? {
+            Some(passphrase) => passphrase,
+            None => secret_input::prompt_hidden("Passphrase for the message: ")?,
         };
+        if passphrase.is_empty() {
+            return Err("Deniable payloads need a passphrase".into());
+        }
+
+        let Some(decoy) = decoy else {
+            return deniable::seal(&[(message, &passphrase)]);
+        };
+        let decoy_passphrase = match decoy_passphrase_file {
+            Some(path) => secret_input::passphrase(Some(path))?.unwrap(),
+            None => secret_input::prompt_hidden("Passphrase for the decoy: ")?,
+        };
+        if decoy_passphrase.is_empty() || decoy_passphrase == passphrase {
+            return Err("The decoy needs its own, different passphrase".into());
+        }
+
+        deniable::seal(&[(message, &passphrase), (decoy.as_bytes(), &decoy_passphrase)])
+    }
+
+    fn embed_shares(data: &[u8], chunk_type: &ChunkType, split: Split, placement: Placement, file_path: &str, images: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let covers: Vec<&str> = std::iter::once(file_path).chain(images.iter().map(String::as_str)).collect();
+        if covers.contains(&"-") {
+            return Err("--split writes the shares back into each image, so it cannot read one from stdin".into());
+        }
+        if covers.len() != split.shares as usize {
+            return Err(format!("--split {split} needs {} images, pass the others with --image", split.shares).into());
+        }
+
+        let mut seen = HashSet::new();
+        for cover in &covers {
+            let canonical = fs::canonicalize(cover).map_err(|e| format!("Cannot read {cover}: {e}"))?;
+            if !seen.insert(canonical) {
+                return Err(format!("{cover} is given more than once, every share needs its own image").into());
+            }
+        }
+
+        let mut pngs = Vec::with_capacity(covers.len());
+        for cover in &covers {
+            let png = Self::read_file(cover.to_string()).map_err(|e| format!("Cannot read {cover}: {e}"))?;
+            if png.chunk_by_type(&chunk_type.to_string()).is_some() {
+                return Err(format!("{cover} already has a {chunk_type} chunk").into());
+            }
+            if png.chunks().iter().any(|chunk| !chunk.crc_is_valid()) {
+                return Err(format!("{cover} has a chunk with a bad CRC").into());
+            }
+            pngs.push(png);
+        }
+
+        let mut outputs = Vec::with_capacity(covers.len());
+        for (mut png, share) in pngs.into_iter().zip(shamir::split(data, split)) {
+            png.insert_chunk(Chunk::new(chunk_type.clone(), share.as_bytes()), placement)?;
+            outputs.push((png.as_bytes(), share.index));
+        }
+        for (cover, (bytes, index)) in covers.iter().zip(outputs) {
+            File::create(cover)?.write_all(&bytes)?;
+            eprintln!("Wrote share {index} of {split} to {cover}");
+        }
         Ok(())
     }
 
+    fn combine_shares(first: &[u8], chunk_type: &ChunkType, images: &[String]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut shares = vec![Share::try_from(first)?];
+        for image in images {
+            let png = Self::read_file(image.clone())?;
+            let Some(chunk) = png.chunk_by_type(&chunk_type.to_string()) else {
+                return Err(format!("{image} has no {chunk_type} chunk").into());
+            };
+            shares.push(Share::try_from(chunk.data())?);
+        }
+        Ok(shamir::combine(&shares)?)
+    }
+
+    fn decrypt_legacy(data: &str, sources: &SecretOptions, nonce: Option<&str>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let key = match sources.key()? {
+            Some(key) => key,
+            None => secret_input::parse_key(&secret_input::prompt_hidden("Enter key: ")?)?,
+        };
+        let nonce = match nonce {
+            Some(nonce) => nonce.to_string(),
+            None => secret_input::prompt("Enter nonce: ")?,
+        };
+        let nonce = hex::decode(nonce.trim()).map_err(|_| "Nonce is not valid hex")?;
+        if nonce.len() != 15 {
+            return Err("Nonce must be 15 bytes".into());
+        }
+        let nonce: [u8; 15] = Self::convert_to_fixed_slice(&nonce);
+        let plaintext = Cipher::DeoxysII256.decrypt(&key, &nonce, hex::decode(data)?.as_ref(), &[])?;
+        Ok(plaintext)
+    }
+
+    fn write_file(png: &Png, output_file: Option<&str>, file_path: &str) -> io::Result<()> {
+        Self::write_bytes(output_file.unwrap_or(file_path), &png.as_bytes())
+    }
+
     pub fn convert_to_fixed_slice(v: &[u8]) -> [u8; 15] {
         v.try_into().unwrap()
     }
 
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::Cli;
+    use crate::testing::{self, TempDir};
+    use clap::Parser;
+
+    #[test]
+    fn test_dash_means_standard_streams() {
+        let dir = TempDir::new("streams");
+        let path = dir.path("image.png");
+
+        assert_eq!(Command::read_bytes_from("-", io::Cursor::new(b"from stdin")).unwrap(), b"from stdin");
+        let mut stdout = Vec::new();
+        Command::write_bytes_to("-", b"to stdout", &mut stdout).unwrap();
+        assert_eq!(stdout, b"to stdout");
+        assert!(!Path::new("-").exists());
+
+        let mut stdout = Vec::new();
+        Command::write_bytes_to(&path, b"to file", &mut stdout).unwrap();
+        assert!(stdout.is_empty());
+        assert_eq!(Command::read_bytes_from(&path, io::Cursor::new(b"from stdin")).unwrap(), b"to file");
+    }
+
+    #[test]
+    fn test_validate() {
+        let chunk = |chunk_type: &str| testing::chunk(chunk_type, b"data");
+        assert!(Command::validate(&testing::minimal_png()).is_ok());
+        assert!(Command::validate(&Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IDAT"), chunk("ruSt"), chunk("IEND")])).is_ok());
+        assert!(Command::validate(&Png::from_chunks(vec![chunk("IHDR"), chunk("ruSt"), chunk("IEND")])).is_err());
+        assert!(Command::validate(&Png::from_chunks(vec![chunk("IDAT"), chunk("IHDR"), chunk("IEND")])).is_err());
+        assert!(Command::validate(&Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND"), chunk("ruSt")])).is_err());
+
+        let split = Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("ruSt"), chunk("IDAT"), chunk("IEND")]);
+        assert_eq!(Command::validate(&split).unwrap_err().to_string(), "IDAT chunks are not consecutive");
+
+        let mut corrupt = testing::minimal_png();
+        corrupt.insert_chunk(chunk("ruSt"), Placement::AfterIhdr).unwrap();
+        let mut bytes = corrupt.as_bytes();
+        let last = bytes.len() - 13;
+        bytes[last] ^= 1;
+        assert!(Command::validate(&Png::try_from(bytes.as_slice()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_batch_decode_restores_files() {
+        let dir = TempDir::new("batch-decode");
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let attachment = Attachment {
+            name: String::from("notes.txt"),
+            mime: String::from("text/plain"),
+            data: b"notes".to_vec(),
+        };
+        fs::create_dir_all(dir.join("in")).unwrap();
+        for (name, data) in [("a.png", attachment.as_bytes()), ("b.png", b"message".to_vec()), ("c.png", attachment.as_bytes())] {
+            let mut png = testing::minimal_png();
+            png.insert_chunk(Chunk::new(chunk_type.clone(), data), Placement::BeforeIend).unwrap();
+            fs::write(dir.join("in").join(name), png.as_bytes()).unwrap();
+        }
+        let input = dir.path("in");
+        let output = dir.path("out");
+
+        let decode = |extra: &[&str]| {
+            let arguments = ["steganopng", "batch", "decode", "ruSt", input.as_str()].into_iter().chain(extra.iter().copied());
+            Cli::try_parse_from(arguments).unwrap().command.execute()
+        };
+        assert!(decode(&[]).is_err());
+        decode(&["--output-dir", &output]).unwrap();
+        assert_eq!(fs::read(dir.join("out").join("a").join("notes.txt")).unwrap(), b"notes");
+        assert_eq!(fs::read(dir.join("out").join("c").join("notes.txt")).unwrap(), b"notes");
+        assert_eq!(fs::read(dir.join("out").join("b.txt")).unwrap(), b"message");
+        assert!(!dir.join("out").join("a.bin").exists());
+    }
+
+    #[test]
+    fn test_decode_requires_a_signature_when_signer_is_given() {
+        let dir = TempDir::new("signer");
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let key_file = KeyFile::generate("ed25519").unwrap();
+        let verifier = key_file.public_key().unwrap();
+        let KeyFile::Signing(signer) = key_file else {
+            panic!("Expected a signing key");
+        };
+        let key = Secret::Key(*GenericArray::from_slice(&[42; 32]));
+        let signed = Envelope::seal(b"signed", &[key], Cipher::DeoxysII256, Padding::Padme, Some((&signer, &chunk_type)), None).unwrap();
+        let payloads = [
+            ("plain.png", b"plain message".to_vec()),
+            ("deniable.png", deniable::seal(&[(b"deniable".as_slice(), "passphrase")]).unwrap()),
+            ("signed.png", signed.to_chunk_data()),
+        ];
+        for (name, data) in &payloads {
+            let mut png = testing::minimal_png();
+            png.insert_chunk(Chunk::new(chunk_type.clone(), data.clone()), Placement::BeforeIend).unwrap();
+            fs::write(dir.join(name), png.as_bytes()).unwrap();
+        }
+
+        let decode = |name: &str, extra: &[&str]| {
+            let path = dir.path(name);
+            let arguments = ["steganopng", "decode", path.as_str(), "ruSt", "--signer", verifier.as_str()].into_iter().chain(extra.iter().copied());
+            Cli::try_parse_from(arguments).unwrap().command.execute()
+        };
+        assert_eq!(decode("plain.png", &["--no-encrypt"]).unwrap_err().to_string(), "Payload is not signed");
+        assert_eq!(decode("plain.png", &["--yes"]).unwrap_err().to_string(), "Payload is not signed");
+        assert_eq!(decode("deniable.png", &["--yes"]).unwrap_err().to_string(), "Payload is not signed");
+        decode("signed.png", &["--key", &"2a".repeat(32)]).unwrap();
+    }
+
+    #[test]
+    fn test_decode_baseline_payload() {
+        let dir = TempDir::new("baseline");
+        let mut png = testing::minimal_png();
+        let data = b"818e0c1249d5d3c936fd9faf5ad04aae33afb724cd5c845c488de444de271ae1e1aacb5244b248".to_vec();
+        png.insert_chunk(Chunk::new(ChunkType::from_str("ruSt").unwrap(), data), Placement::BeforeIend).unwrap();
+        let path = dir.path("baseline.png");
+        let output = dir.path("message.txt");
+        fs::write(&path, png.as_bytes()).unwrap();
+
+        let key = "2a".repeat(32);
+        let nonce = "07".repeat(15);
+        let cli = Cli::try_parse_from(["steganopng", "decode", &path, "ruSt", "--key", &key, "--nonce", &nonce, "--yes", "--output", &output]).unwrap();
+        cli.command.execute().unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"hidden before envelopes");
+
+        let cli = Cli::try_parse_from(["steganopng", "decode", &path, "ruSt", "--key", &key, "--nonce", &"00".repeat(15), "--yes"]).unwrap();
+        assert!(cli.command.execute().is_err());
+    }
+
+    #[test]
+    fn test_rekey_chunk() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let key = |byte: u8| Secret::Key(*GenericArray::from_slice(&[byte; 32]));
+        let mut png = testing::minimal_png();
+        let carrier = png.carrier_binding(&chunk_type);
+        let envelope = Envelope::seal(b"secret message", &[key(1)], Cipher::Aes256GcmSiv, Padding::Buckets, None, Some(&carrier)).unwrap();
+        png.insert_chunk(Chunk::new(chunk_type.clone(), envelope.to_chunk_data()), Placement::AfterIhdr).unwrap();
+        let before = png.as_bytes();
+
+        assert!(Command::rekey_chunk(&mut png, &chunk_type, |_| Ok(vec![key(9)]), || Ok(vec![key(2)]), None, None).is_err());
+        assert_eq!(png.as_bytes(), before);
+
+        Command::rekey_chunk(&mut png, &chunk_type, |_| Ok(vec![key(1)]), || Ok(vec![key(2)]), None, None).unwrap();
+        let index = png.chunks().iter().position(|chunk| chunk.chunk_type() == &chunk_type).unwrap();
+        assert_eq!(index, 1);
+        let others = |png: &Png| png.chunks().iter().filter(|chunk| chunk.chunk_type() != &chunk_type).map(Chunk::as_bytes).collect::<Vec<_>>();
+        assert_eq!(others(&png), others(&Png::try_from(before.as_slice()).unwrap()));
+
+        let rekeyed = Envelope::from_chunk_data(png.chunks()[index].data()).unwrap().unwrap();
+        assert_eq!(rekeyed.cipher, Cipher::Aes256GcmSiv);
+        assert_eq!(rekeyed.padding, Padding::Buckets);
+        assert!(rekeyed.is_bound());
+        assert!(rekeyed.open(&[key(1)], &carrier).is_err());
+        assert_eq!(rekeyed.open(&[key(2)], &carrier).unwrap(), b"secret message");
+        assert!(rekeyed.open(&[key(2)], b"another carrier").is_err());
+
+        Command::rekey_chunk(&mut png, &chunk_type, |_| Ok(vec![key(2)]), || Ok(vec![key(3)]), Some(Cipher::XChaCha20Poly1305), None).unwrap();
+        let rekeyed = Envelope::from_chunk_data(png.chunk_by_type("ruSt").unwrap().data()).unwrap().unwrap();
+        assert_eq!(rekeyed.cipher, Cipher::XChaCha20Poly1305);
+        assert_eq!(rekeyed.open(&[key(3)], &carrier).unwrap(), b"secret message");
+    }
+
+    #[test]
+    fn test_embed_shares_checks_every_cover_first() {
+        let dir = TempDir::new("shares");
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let covers: Vec<String> = (1..=3).map(|n| dir.join(format!("q{n}.png")).display().to_string()).collect();
+        for cover in &covers[..2] {
+            fs::write(cover, testing::minimal_png().as_bytes()).unwrap();
+        }
+        let mut taken = testing::minimal_png();
+        taken.append_chunk(Chunk::new(chunk_type.clone(), b"already here".to_vec())).unwrap();
+        fs::write(&covers[2], taken.as_bytes()).unwrap();
+
+        let split = Split::from_str("2-of-3").unwrap();
+        assert!(Command::embed_shares(b"secret", &chunk_type, split, Placement::BeforeIend, &covers[0], &covers[1..]).is_err());
+        for cover in &covers[..2] {
+            assert_eq!(fs::read(cover).unwrap(), testing::minimal_png().as_bytes());
+        }
+
+        fs::write(&covers[2], testing::minimal_png().as_bytes()).unwrap();
+        let same = [covers[1].clone(), dir.join(".").join("q1.png").display().to_string()];
+        assert!(Command::embed_shares(b"secret", &chunk_type, split, Placement::BeforeIend, &covers[0], &same).is_err());
+        for cover in &covers {
+            assert_eq!(fs::read(cover).unwrap(), testing::minimal_png().as_bytes());
+        }
+
+        Command::embed_shares(b"secret", &chunk_type, split, Placement::BeforeIend, &covers[0], &covers[1..]).unwrap();
+        let first = Command::read_file(covers[1].clone()).unwrap();
+        let combined = Command::combine_shares(first.chunk_by_type("ruSt").unwrap().data(), &chunk_type, &covers[2..]).unwrap();
+        assert_eq!(combined, b"secret");
+    }
+}
@@ -0,0 +1,152 @@
+use std::convert::TryFrom;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub name: String,
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+impl TryFrom<&[u8]> for Attachment {
+    type Error = &'static str;
+
+    fn try_from(a: &[u8]) -> Result<Self, Self::Error> {
+        if !Attachment::matches(a) {
+            return Err("Payload is not a file");
+        }
+        if a.get(4) != Some(&Attachment::VERSION) {
+            return Err("Unsupported file payload version");
+        }
+
+        let mut count: usize = 5;
+        let name = take_string(a, &mut count)?;
+        let mime = take_string(a, &mut count)?;
+        if Path::new(&name).file_name().and_then(|file_name| file_name.to_str()) != Some(name.as_str()) {
+            return Err("File payload has an invalid name");
+        }
+
+        Ok(Self {
+            name,
+            mime,
+            data: a[count..].to_vec(),
+        })
+    }
+}
+
+impl Attachment {
+    pub const MAGIC: &'static [u8; 4] = b"SPNF";
+    pub const VERSION: u8 = 1;
+
+    pub fn read(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let Some(name) = Path::new(path).file_name().and_then(|name| name.to_str()) else {
+            return Err(format!("{path} has no usable file name").into());
+        };
+        let data = std::fs::read(path).map_err(|e| format!("Cannot read {path}: {e}"))?;
+        Ok(Self {
+            name: name.to_string(),
+            mime: guess_mime(name, &data).to_string(),
+            data,
+        })
+    }
+
+    pub fn matches(data: &[u8]) -> bool {
+        data.starts_with(Attachment::MAGIC)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        Attachment::MAGIC
+            .iter()
+            .chain([Attachment::VERSION].iter())
+            .chain((self.name.len() as u16).to_be_bytes().iter())
+            .chain(self.name.as_bytes().iter())
+            .chain((self.mime.len() as u16).to_be_bytes().iter())
+            .chain(self.mime.as_bytes().iter())
+            .chain(self.data.iter())
+            .copied()
+            .collect()
+    }
+}
+
+pub fn guess_mime(name: &str, data: &[u8]) -> &'static str {
+    let signatures: [(&[u8], &str); 7] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF8", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    ];
+    if let Some((_, mime)) = signatures.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return mime;
+    }
+
+    let extension = Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "txt" | "md" => "text/plain",
+        "json" => "application/json",
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "xml" => "application/xml",
+        _ if std::str::from_utf8(data).is_ok() => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+fn take_string(a: &[u8], count: &mut usize) -> Result<String, &'static str> {
+    let Some(length) = a.get(*count..*count + 2) else {
+        return Err("File payload is truncated");
+    };
+    let length = u16::from_be_bytes(length.try_into().unwrap()) as usize;
+    let Some(bytes) = a.get(*count + 2..*count + 2 + length) else {
+        return Err("File payload is truncated");
+    };
+    *count += 2 + length;
+    String::from_utf8(bytes.to_vec()).map_err(|_| "File payload metadata is not valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_attachment() -> Attachment {
+        Attachment {
+            name: String::from("secret.zip"),
+            mime: String::from("application/zip"),
+            data: vec![0x50, 0x4b, 0x03, 0x04, 0, 0xff, 0x80],
+        }
+    }
+
+    #[test]
+    fn test_attachment_round_trip() {
+        let attachment = testing_attachment();
+        let bytes = attachment.as_bytes();
+        assert!(Attachment::matches(&bytes));
+        assert_eq!(Attachment::try_from(bytes.as_slice()).unwrap(), attachment);
+    }
+
+    #[test]
+    fn test_invalid_attachment() {
+        assert!(Attachment::try_from(&b"plain message"[..]).is_err());
+        let bytes = testing_attachment().as_bytes();
+        assert!(Attachment::try_from(&bytes[..8]).is_err());
+
+        let mut traversal = testing_attachment();
+        traversal.name = String::from("../../.bashrc");
+        assert!(Attachment::try_from(traversal.as_bytes().as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_guess_mime() {
+        assert_eq!(guess_mime("archive", b"PK\x03\x04rest"), "application/zip");
+        assert_eq!(guess_mime("photo.bin", b"\xff\xd8\xff\xe0"), "image/jpeg");
+        assert_eq!(guess_mime("data.json", b"{}"), "application/json");
+        assert_eq!(guess_mime("notes", b"hello"), "text/plain");
+        assert_eq!(guess_mime("blob", &[0xff, 0xfe, 0x00]), "application/octet-stream");
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Input {
+    pub path: PathBuf,
+    pub relative: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Outcome {
+    pub input: String,
+    pub output: Option<String>,
+    pub ok: bool,
+    pub message: String,
+}
+
+pub fn collect_inputs(patterns: &[String]) -> Result<Vec<Input>, Box<dyn std::error::Error>> {
+    let mut inputs = Vec::new();
+    for pattern in patterns {
+        let path = Path::new(pattern);
+        if path.is_dir() {
+            walk(path, path, &mut inputs)?;
+        } else if path.is_file() {
+            inputs.push(Input {
+                path: path.to_path_buf(),
+                relative: PathBuf::from(path.file_name().ok_or_else(|| format!("{pattern} has no file name"))?),
+            });
+        } else if pattern.contains(['*', '?', '[']) {
+            let base = glob_base(pattern);
+            let mut matched = false;
+            for path in glob::glob(pattern).map_err(|e| format!("Invalid pattern {pattern}: {e}"))? {
+                let path = path?;
+                if path.is_file() {
+                    let relative = path.strip_prefix(&base).unwrap_or(&path).to_path_buf();
+                    inputs.push(Input { path, relative });
+                    matched = true;
+                }
+            }
+            if !matched {
+                return Err(format!("{pattern} matches no files").into());
+            }
+        } else {
+            return Err(format!("{pattern} does not exist").into());
+        }
+    }
+
+    let mut seen = HashSet::new();
+    inputs.retain(|input| seen.insert(input.path.clone()));
+    Ok(inputs)
+}
+
+pub fn run<F>(inputs: &[Input], output_dir: Option<&Path>, jobs: Option<usize>, task: F) -> Result<Vec<Outcome>, Box<dyn std::error::Error>>
+where
+    F: Fn(&Path, Option<&Path>) -> Result<String, Box<dyn std::error::Error>> + Sync,
+{
+    let mut relatives = HashSet::new();
+    if let Some(input) = inputs.iter().find(|input| output_dir.is_some() && !relatives.insert(&input.relative)) {
+        return Err(format!("More than one input maps to {}", input.relative.display()).into());
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()?;
+    let outcomes = pool.install(|| {
+        inputs
+            .par_iter()
+            .map(|input| {
+                let output = output_dir.map(|dir| dir.join(&input.relative));
+                let result = match output.as_deref().and_then(Path::parent) {
+                    Some(parent) => fs::create_dir_all(parent)
+                        .map_err(|e| format!("Cannot create {}: {e}", parent.display()).into())
+                        .and_then(|_| task(&input.path, output.as_deref())),
+                    None => task(&input.path, output.as_deref()),
+                };
+                let (ok, message) = match result {
+                    Ok(message) => (true, message),
+                    Err(e) => (false, e.to_string()),
+                };
+                Outcome {
+                    input: input.path.display().to_string(),
+                    output: output.map(|output| output.display().to_string()),
+                    ok,
+                    message,
+                }
+            })
+            .collect()
+    });
+    Ok(outcomes)
+}
+
+pub fn summarize(outcomes: &[Outcome], summary_file: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    for outcome in outcomes.iter().filter(|outcome| !outcome.ok) {
+        eprintln!("FAILED {}: {}", outcome.input, outcome.message);
+    }
+    let failed = outcomes.iter().filter(|outcome| !outcome.ok).count();
+    eprintln!("{} succeeded, {} failed", outcomes.len() - failed, failed);
+
+    if let Some(path) = summary_file {
+        fs::write(path, format!("{}\n", serde_json::to_string_pretty(outcomes)?))
+            .map_err(|e| format!("Cannot write summary {path}: {e}"))?;
+    }
+    if failed > 0 {
+        return Err(format!("{failed} of {} files failed", outcomes.len()).into());
+    }
+    Ok(())
+}
+
+fn walk(root: &Path, dir: &Path, inputs: &mut Vec<Input>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = fs::read_dir(dir)
+        .map_err(|e| format!("Cannot read {}: {e}", dir.display()))?
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            walk(root, &path, inputs)?;
+        } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png")) {
+            let relative = path.strip_prefix(root).unwrap().to_path_buf();
+            inputs.push(Input { path, relative });
+        }
+    }
+    Ok(())
+}
+
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| !component.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn testing_tree() -> TempDir {
+        let dir = TempDir::new("batch");
+        fs::create_dir_all(dir.join("renders").join("night")).unwrap();
+        for file in ["renders/a.png", "renders/b.PNG", "renders/notes.txt", "renders/night/c.png"] {
+            fs::write(dir.join(file), file).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_collect_directory() {
+        let dir = testing_tree();
+        let inputs = collect_inputs(&[dir.join("renders").display().to_string()]).unwrap();
+        let relatives: Vec<PathBuf> = inputs.iter().map(|input| input.relative.clone()).collect();
+        assert_eq!(relatives, [PathBuf::from("a.png"), PathBuf::from("b.PNG"), Path::new("night").join("c.png")]);
+    }
+
+    #[test]
+    fn test_collect_glob() {
+        let dir = testing_tree();
+        let pattern = dir.join("renders").join("**").join("*.png").display().to_string();
+        let inputs = collect_inputs(&[pattern.clone(), pattern]).unwrap();
+        let relatives: Vec<PathBuf> = inputs.iter().map(|input| input.relative.clone()).collect();
+        assert_eq!(relatives, [PathBuf::from("a.png"), Path::new("night").join("c.png")]);
+
+        assert!(collect_inputs(&[dir.join("*.jpg").display().to_string()]).is_err());
+        assert!(collect_inputs(&[dir.join("missing.png").display().to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_colliding_outputs_are_errors() {
+        let dir = testing_tree();
+        let files = [dir.join("renders").join("a.png"), dir.join("renders").join("night").join("a.png")];
+        fs::copy(&files[0], &files[1]).unwrap();
+        let patterns: Vec<String> = files.iter().map(|file| file.display().to_string()).collect();
+        let inputs = collect_inputs(&patterns).unwrap();
+        assert!(run(&inputs, None, None, |_, _| Ok(String::new())).is_ok());
+        assert!(run(&inputs, Some(&dir.join("out")), None, |_, _| Ok(String::new())).is_err());
+    }
+
+    #[test]
+    fn test_run_and_summarize() {
+        let dir = testing_tree();
+        let inputs = collect_inputs(&[dir.join("renders").display().to_string()]).unwrap();
+        let output_dir = dir.join("out");
+        let outcomes = run(&inputs, Some(&output_dir), Some(2), |input, output| {
+            let data = fs::read(input)?;
+            if data.ends_with(b"PNG") {
+                return Err("not a png".into());
+            }
+            fs::write(output.unwrap(), data)?;
+            Ok(String::from("copied"))
+        })
+        .unwrap();
+
+        assert_eq!(outcomes.iter().filter(|outcome| outcome.ok).count(), 2);
+        assert_eq!(fs::read(output_dir.join("night").join("c.png")).unwrap(), b"renders/night/c.png");
+        assert!(!output_dir.join("b.PNG").exists());
+
+        let summary = dir.join("summary.json");
+        assert!(summarize(&outcomes, Some(summary.to_str().unwrap())).is_err());
+        let summary: serde_json::Value = serde_json::from_slice(&fs::read(summary).unwrap()).unwrap();
+        assert_eq!(summary[1]["ok"], false);
+        assert_eq!(summary[1]["message"], "not a png");
+        assert!(summarize(&outcomes[..1], None).is_ok());
+    }
+}
@@ -23,7 +23,7 @@ impl TryFrom<&[u8]> for Chunk {
         let crc32 = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
         let length: u32 = u32::from_be_bytes(c[..4].try_into().unwrap());
         let chunk_type: ChunkType = ChunkType::try_from(ChunkType::convert_to_fixed_slice(&c[4..8])).unwrap();
-        let data: Vec<u8> = c[8..c.len() - 4].try_into().unwrap();
+        let data: Vec<u8> = c[8..c.len() - 4].into();
         let mut combined = Vec::new();
         combined.extend(chunk_type.bytes().to_vec());
         combined.extend(data.clone());
@@ -88,6 +88,14 @@ impl Chunk {
         crc
     }
 
+    pub fn crc_is_valid(&self) -> bool {
+        let crc32 = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+        let mut combined = Vec::new();
+        combined.extend(self.chunk_type.bytes().to_vec());
+        combined.extend(self.data.clone());
+        self.crc == crc32.checksum(&combined)
+    }
+
     pub fn data_as_string(&self) -> Result<String, FromUtf8Error> {
         String::from_utf8(self.data.clone())
     }
@@ -164,6 +172,14 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_chunk_crc_is_valid() {
+        let mut chunk = testing_chunk();
+        assert!(chunk.crc_is_valid());
+        chunk.crc ^= 1;
+        assert!(!chunk.crc_is_valid());
+    }
+
     #[test]
     fn test_valid_chunk_from_bytes() {
         let data_length: u32 = 42;
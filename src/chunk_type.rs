@@ -38,6 +38,13 @@ impl FromStr for ChunkType {
     type Err = &'static str;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 4  {
+            return Err("ChunkType codes are 4 bytes long.");
+        }
+        if !s.is_ascii() {
+            return Err("ChunkType codes are restricted to consist of uppercase or lowercase ASCII letters.");
+        }
+
         let ancillary    = s.chars().next().unwrap();
         let private      = s.chars().nth(1).unwrap();
         let reserved     = s.chars().nth(2).unwrap();
@@ -49,10 +56,6 @@ impl FromStr for ChunkType {
             return Err("ChunkType codes are restricted to consist of uppercase or lowercase ASCII letters.");
         };
 
-        if s.len() != 4  {
-            return Err("ChunkType codes are 4 bytes long.");
-        }
-
         Ok(Self {
             ancillary,
             private,
@@ -90,22 +93,18 @@ impl ChunkType {
         valid(ancillary as u8) && valid(private as u8) && (reserved as u8 >= 65 && reserved as u8 <= 90) && valid(safe_to_copy as u8)
     }
 
-    #[cfg(test)]
     pub fn is_critical(&self) -> bool {
         self.ancillary as u8 >= 65 && self.ancillary as u8 <= 90
     }
 
-    #[cfg(test)]
     pub fn is_public(&self) -> bool {
         self.private as u8 >= 65 && self.private as u8 <= 90
     }
 
-    #[cfg(test)]
     pub fn is_reserved_bit_valid(&self) -> bool {
         self.reserved as u8 >= 65 && self.reserved as u8 <= 90
     }
 
-    #[cfg(test)]
     pub fn is_safe_to_copy(&self) -> bool {
         self.safe_to_copy as u8 >= 97 && self.safe_to_copy as u8 <= 122
     }
@@ -195,6 +194,9 @@ mod tests {
 
         let chunk = ChunkType::from_str("Ru1t");
         assert!(chunk.is_err());
+
+        assert!(ChunkType::from_str("éé").is_err());
+        assert!(ChunkType::from_str("aé").is_err());
     }
 
     #[test]
@@ -0,0 +1,146 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use aes_gcm_siv::Aes256GcmSiv;
+use chacha20poly1305::XChaCha20Poly1305;
+use deoxys::{
+    aead::{Aead, KeyInit, Payload},
+    DeoxysII256,
+};
+use generic_array::GenericArray;
+use generic_array::typenum::U32;
+use rand::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    DeoxysII256,
+    Aes256GcmSiv,
+    XChaCha20Poly1305,
+}
+
+impl TryFrom<u8> for Cipher {
+    type Error = &'static str;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            1 => Ok(Self::DeoxysII256),
+            2 => Ok(Self::Aes256GcmSiv),
+            3 => Ok(Self::XChaCha20Poly1305),
+            _ => Err("Unsupported envelope cipher"),
+        }
+    }
+}
+
+impl FromStr for Cipher {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "deoxys-ii-256" | "deoxys" => Ok(Self::DeoxysII256),
+            "aes-256-gcm-siv" | "aes" => Ok(Self::Aes256GcmSiv),
+            "xchacha20-poly1305" | "xchacha20" => Ok(Self::XChaCha20Poly1305),
+            _ => Err("Unknown cipher, expected deoxys-ii-256, aes-256-gcm-siv or xchacha20-poly1305"),
+        }
+    }
+}
+
+impl fmt::Display for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeoxysII256 => write!(f, "deoxys-ii-256"),
+            Self::Aes256GcmSiv => write!(f, "aes-256-gcm-siv"),
+            Self::XChaCha20Poly1305 => write!(f, "xchacha20-poly1305"),
+        }
+    }
+}
+
+impl Cipher {
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::DeoxysII256 => 1,
+            Self::Aes256GcmSiv => 2,
+            Self::XChaCha20Poly1305 => 3,
+        }
+    }
+
+    pub fn nonce_len(&self) -> usize {
+        match self {
+            Self::DeoxysII256 => 15,
+            Self::Aes256GcmSiv => 12,
+            Self::XChaCha20Poly1305 => 24,
+        }
+    }
+
+    pub fn generate_nonce(&self) -> Vec<u8> {
+        let mut rng = rand::thread_rng();
+        (0..self.nonce_len()).map(|_| rng.gen()).collect()
+    }
+
+    pub fn encrypt(&self, key: &GenericArray<u8, U32>, nonce: &[u8], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>, &'static str> {
+        if nonce.len() != self.nonce_len() {
+            return Err("Invalid nonce length");
+        }
+        let payload = Payload { msg, aad };
+        match self {
+            Self::DeoxysII256 => DeoxysII256::new(key).encrypt(GenericArray::from_slice(nonce), payload),
+            Self::Aes256GcmSiv => Aes256GcmSiv::new(key).encrypt(GenericArray::from_slice(nonce), payload),
+            Self::XChaCha20Poly1305 => XChaCha20Poly1305::new(key).encrypt(GenericArray::from_slice(nonce), payload),
+        }
+        .map_err(|_| "Encryption failed")
+    }
+
+    pub fn decrypt(&self, key: &GenericArray<u8, U32>, nonce: &[u8], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>, &'static str> {
+        if nonce.len() != self.nonce_len() {
+            return Err("Invalid nonce length");
+        }
+        let payload = Payload { msg, aad };
+        match self {
+            Self::DeoxysII256 => DeoxysII256::new(key).decrypt(GenericArray::from_slice(nonce), payload),
+            Self::Aes256GcmSiv => Aes256GcmSiv::new(key).decrypt(GenericArray::from_slice(nonce), payload),
+            Self::XChaCha20Poly1305 => XChaCha20Poly1305::new(key).decrypt(GenericArray::from_slice(nonce), payload),
+        }
+        .map_err(|_| "Payload failed authentication")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CIPHERS: [Cipher; 3] = [Cipher::DeoxysII256, Cipher::Aes256GcmSiv, Cipher::XChaCha20Poly1305];
+
+    #[test]
+    fn test_cipher_ids_round_trip() {
+        for cipher in CIPHERS {
+            assert_eq!(Cipher::try_from(cipher.id()).unwrap(), cipher);
+        }
+        assert!(Cipher::try_from(0).is_err());
+    }
+
+    #[test]
+    fn test_cipher_names_round_trip() {
+        for cipher in CIPHERS {
+            assert_eq!(Cipher::from_str(&cipher.to_string()).unwrap(), cipher);
+        }
+        assert!(Cipher::from_str("rot13").is_err());
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let key = GenericArray::from_slice(&[42; 32]);
+        for cipher in CIPHERS {
+            let nonce = cipher.generate_nonce();
+            assert_eq!(nonce.len(), cipher.nonce_len());
+
+            let ciphertext = cipher.encrypt(key, &nonce, b"message", b"header").unwrap();
+            assert_eq!(cipher.decrypt(key, &nonce, &ciphertext, b"header").unwrap(), b"message");
+            assert!(cipher.decrypt(key, &nonce, &ciphertext, b"other").is_err());
+        }
+    }
+
+    #[test]
+    fn test_wrong_nonce_length() {
+        let key = GenericArray::from_slice(&[42; 32]);
+        assert!(Cipher::Aes256GcmSiv.encrypt(key, &[0; 15], b"message", b"").is_err());
+    }
+}
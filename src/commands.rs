@@ -0,0 +1,403 @@
+use std::str::FromStr;
+use clap::{Parser, Subcommand};
+use crate::chunk_type::ChunkType;
+use crate::cipher::Cipher;
+use crate::listing::Format;
+use crate::padding::Padding;
+use crate::png::Placement;
+use crate::shamir::Split;
+
+/// Hide, recover and manage messages stored in PNG chunks
+#[derive(Parser)]
+#[command(name = "steganopng", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Hide a message in a PNG chunk
+    Encode {
+        /// PNG file or URL to hide the message in, - reads it from stdin
+        file: String,
+        #[arg(value_parser = parse_chunk_type)]
+        chunk_type: ChunkType,
+        #[arg(required_unless_present_any = ["message_file", "attachment"])]
+        message: Option<String>,
+        /// Write the result here instead of overwriting FILE
+        #[arg(conflicts_with = "output")]
+        output_file: Option<String>,
+        /// Read the message from this file, - reads it from stdin
+        #[arg(long, conflicts_with = "message")]
+        message_file: Option<String>,
+        /// Hide this file together with its name and MIME type
+        #[arg(long = "file", conflicts_with_all = ["message", "message_file"])]
+        attachment: Option<String>,
+        #[command(flatten)]
+        output: OutputOptions,
+        #[command(flatten)]
+        secrets: SecretOptions,
+        /// Where to insert the chunk: random, after-ihdr or before-iend
+        #[arg(long, default_value_t = Placement::Random)]
+        placement: Placement,
+        /// Payload cipher: deoxys-ii-256, aes-256-gcm-siv or xchacha20-poly1305
+        #[arg(long)]
+        cipher: Option<Cipher>,
+        /// Plaintext padding: padme, buckets or none
+        #[arg(long)]
+        padding: Option<Padding>,
+        /// Seal the message to an X25519 public key, may be repeated
+        #[arg(long = "recipient")]
+        recipients: Vec<String>,
+        /// Sign the payload with an ed25519 key file
+        #[arg(long)]
+        sign: Option<String>,
+        /// Bind the payload to this image's pixel data
+        #[arg(long)]
+        bind: bool,
+        /// Store a decoy message that opens with a second passphrase
+        #[arg(long, conflicts_with_all = ["recipients", "sign", "bind", "split", "cipher", "padding", "key_files", "identities", "key_names", "key_fd", "key"])]
+        decoy: Option<String>,
+        #[arg(long, requires = "decoy")]
+        decoy_passphrase_file: Option<String>,
+        /// Store the message without any header, padded to a fixed number of slots
+        #[arg(long, conflicts_with_all = ["recipients", "sign", "bind", "split", "cipher", "padding", "key_files", "identities", "key_names", "key_fd", "key"])]
+        deniable: bool,
+        /// Split the payload into shares, for example 3-of-5
+        #[arg(long, requires = "images", conflicts_with_all = ["bind", "output", "output_file"])]
+        split: Option<Split>,
+        /// Additional images to hold shares
+        #[arg(long = "image")]
+        images: Vec<String>,
+        /// Encrypt without asking, using a random key if no secret is given
+        #[arg(long)]
+        encrypt: bool,
+        /// Store the message as plain text
+        #[arg(long, conflicts_with_all = ["encrypt", "recipients", "sign", "bind", "decoy", "deniable", "key_files", "identities", "key_names", "passphrase_file", "key_fd", "key"])]
+        no_encrypt: bool,
+        /// Answer yes to every question
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Recover a hidden message
+    Decode {
+        file: String,
+        #[arg(value_parser = parse_chunk_type)]
+        chunk_type: ChunkType,
+        #[command(flatten)]
+        secrets: SecretOptions,
+        /// Verify the payload signature against this spng-sig- key
+        #[arg(long)]
+        signer: Option<String>,
+        /// Other images holding shares of a split payload
+        #[arg(long = "image")]
+        images: Vec<String>,
+        #[command(flatten)]
+        output: OutputOptions,
+        /// Restore a hidden file into this directory under its original name
+        #[arg(long, conflicts_with = "output")]
+        output_dir: Option<String>,
+        /// Nonce of a payload written by older versions, as hex
+        #[arg(long)]
+        nonce: Option<String>,
+        /// The payload was stored as plain text, print it as is
+        #[arg(long, conflicts_with = "yes")]
+        no_encrypt: bool,
+        /// Answer yes to every question
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Remove a chunk
+    Remove {
+        file: String,
+        #[arg(value_parser = parse_chunk_type)]
+        chunk_type: ChunkType,
+        #[command(flatten)]
+        output: OutputOptions,
+    },
+    /// Re-encrypt a payload for new secrets, keeping its position
+    Rekey {
+        file: String,
+        #[arg(value_parser = parse_chunk_type)]
+        chunk_type: ChunkType,
+        #[command(flatten)]
+        output: OutputOptions,
+        #[command(flatten)]
+        secrets: SecretOptions,
+        /// New symmetric key or identity file, may be repeated
+        #[arg(long = "new-key-file")]
+        new_key_files: Vec<String>,
+        #[arg(long)]
+        new_passphrase_file: Option<String>,
+        /// Also seal the payload to an X25519 public key, may be repeated
+        #[arg(long = "recipient")]
+        recipients: Vec<String>,
+        /// Switch to another payload cipher
+        #[arg(long)]
+        cipher: Option<Cipher>,
+        /// Sign the new payload with an ed25519 key file
+        #[arg(long)]
+        sign: Option<String>,
+    },
+    /// Print every chunk in a PNG
+    Print {
+        file: String,
+    },
+    /// List chunks with their offsets, properties and CRC status
+    List {
+        file: String,
+        /// Output format: text or json
+        #[arg(long, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Write the data of every unknown or private chunk to its own file, with a manifest
+    ExtractAll {
+        file: String,
+        /// Directory to write the chunk files and manifest.json into
+        output_dir: String,
+    },
+    /// Check a payload signature
+    Verify {
+        file: String,
+        #[arg(value_parser = parse_chunk_type)]
+        chunk_type: ChunkType,
+        /// spng-sig- key of the expected signer
+        #[arg(long)]
+        signer: String,
+    },
+    /// Generate a key
+    Keygen {
+        /// symmetric, x25519 or ed25519
+        #[arg(long = "type", default_value = "x25519")]
+        kind: String,
+        /// Write the key here, and any public key next to it
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Manage the local keystore
+    #[command(subcommand)]
+    Key(KeyCommand),
+    /// Encode, decode, remove or validate many images in parallel
+    #[command(subcommand)]
+    Batch(BatchCommand),
+}
+
+#[derive(Subcommand)]
+pub enum KeyCommand {
+    /// Generate or import a named key
+    Add {
+        name: String,
+        /// symmetric, x25519 or ed25519
+        #[arg(long = "type", default_value = "symmetric")]
+        kind: String,
+        /// Import this key file instead of generating a key
+        #[arg(long)]
+        key_file: Option<String>,
+    },
+    /// List stored keys
+    List,
+    /// Delete a stored key
+    Remove {
+        name: String,
+    },
+    /// Print a stored key or write it to a file
+    Export {
+        name: String,
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BatchCommand {
+    /// Hide the same message in every image
+    Encode {
+        #[arg(value_parser = parse_chunk_type)]
+        chunk_type: ChunkType,
+        #[command(flatten)]
+        batch: BatchOptions,
+        /// Write the encoded images here, mirroring the input tree
+        #[arg(long)]
+        output_dir: String,
+        #[arg(short, long, required_unless_present = "message_file")]
+        message: Option<String>,
+        /// Read the message from this file
+        #[arg(long, conflicts_with = "message")]
+        message_file: Option<String>,
+        #[command(flatten)]
+        secrets: SecretOptions,
+        /// Where to insert the chunk: random, after-ihdr or before-iend
+        #[arg(long, default_value_t = Placement::Random)]
+        placement: Placement,
+        /// Payload cipher: deoxys-ii-256, aes-256-gcm-siv or xchacha20-poly1305
+        #[arg(long)]
+        cipher: Option<Cipher>,
+        /// Plaintext padding: padme, buckets or none
+        #[arg(long)]
+        padding: Option<Padding>,
+        /// Seal the message to an X25519 public key, may be repeated
+        #[arg(long = "recipient")]
+        recipients: Vec<String>,
+        /// Sign every payload with an ed25519 key file
+        #[arg(long)]
+        sign: Option<String>,
+        /// Bind each payload to its image's pixel data
+        #[arg(long)]
+        bind: bool,
+        /// Store the message as plain text
+        #[arg(long, conflicts_with_all = ["recipients", "sign", "bind", "key_files", "identities", "key_names", "passphrase_file", "key_fd", "key"])]
+        no_encrypt: bool,
+    },
+    /// Recover the message from every image
+    Decode {
+        #[arg(value_parser = parse_chunk_type)]
+        chunk_type: ChunkType,
+        #[command(flatten)]
+        batch: BatchOptions,
+        /// Write each message here, mirroring the input tree, and restore hidden files into a directory named after their image
+        #[arg(long)]
+        output_dir: Option<String>,
+        #[command(flatten)]
+        secrets: SecretOptions,
+        /// Verify every payload signature against this spng-sig- key
+        #[arg(long)]
+        signer: Option<String>,
+    },
+    /// Remove a chunk from every image
+    Remove {
+        #[arg(value_parser = parse_chunk_type)]
+        chunk_type: ChunkType,
+        #[command(flatten)]
+        batch: BatchOptions,
+        /// Write the cleaned images here, mirroring the input tree
+        #[arg(long)]
+        output_dir: String,
+    },
+    /// Check the structure and CRCs of every image
+    Validate {
+        #[command(flatten)]
+        batch: BatchOptions,
+        /// Also require this chunk
+        #[arg(long, value_parser = parse_chunk_type)]
+        chunk_type: Option<ChunkType>,
+        /// Also verify the chunk's signature against this spng-sig- key
+        #[arg(long, requires = "chunk_type")]
+        signer: Option<String>,
+    },
+}
+
+#[derive(clap::Args)]
+pub struct BatchOptions {
+    /// Directories, PNG files or glob patterns to process
+    #[arg(required = true)]
+    pub inputs: Vec<String>,
+    /// Number of worker threads, one per core by default
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
+    /// Also write a JSON summary of every file here
+    #[arg(long)]
+    pub summary: Option<String>,
+}
+
+#[derive(clap::Args)]
+pub struct OutputOptions {
+    /// Where to write the result instead of FILE, - writes it to stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(clap::Args, Default)]
+pub struct SecretOptions {
+    /// Symmetric key file, may be repeated
+    #[arg(long = "key-file")]
+    pub key_files: Vec<String>,
+    /// X25519 identity file, may be repeated
+    #[arg(long = "identity")]
+    pub identities: Vec<String>,
+    /// Key stored in the keystore, may be repeated
+    #[arg(long = "key-name")]
+    pub key_names: Vec<String>,
+    /// Read the passphrase from the first line of this file
+    #[arg(long)]
+    pub passphrase_file: Option<String>,
+    /// Read the key from this file descriptor
+    #[arg(long)]
+    pub key_fd: Option<String>,
+    /// Symmetric key as hex or SPNG-KEY-
+    #[arg(long)]
+    pub key: Option<String>,
+}
+
+impl Command {
+    pub fn file_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Self::Encode { file, .. }
+            | Self::Decode { file, .. }
+            | Self::Remove { file, .. }
+            | Self::Rekey { file, .. }
+            | Self::Print { file }
+            | Self::List { file, .. }
+            | Self::ExtractAll { file, .. }
+            | Self::Verify { file, .. } => Some(file),
+            Self::Keygen { .. } | Self::Key(_) | Self::Batch(_) => None,
+        }
+    }
+}
+
+fn parse_chunk_type(s: &str) -> Result<ChunkType, String> {
+    let chunk_type = ChunkType::from_str(s)?;
+    if !chunk_type.is_valid() {
+        return Err(format!("{s} is not a valid chunk type, its third letter must be uppercase"));
+    }
+    Ok(chunk_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_encode() {
+        let cli = Cli::try_parse_from(["steganopng", "encode", "cover.png", "ruSt", "message", "--cipher", "aes", "--placement", "before-iend"]).unwrap();
+        let Command::Encode { chunk_type, cipher, placement, .. } = cli.command else {
+            panic!("Expected encode");
+        };
+        assert_eq!(chunk_type.to_string(), "ruSt");
+        assert_eq!(cipher, Some(Cipher::Aes256GcmSiv));
+        assert_eq!(placement, Placement::BeforeIend);
+
+        let cli = Cli::try_parse_from(["steganopng", "list", "cover.png", "--format", "json"]).unwrap();
+        assert!(matches!(cli.command, Command::List { format: Format::Json, .. }));
+
+        let cli = Cli::try_parse_from(["steganopng", "batch", "encode", "ruSt", "renders", "shots/*.png", "--output-dir", "out", "-m", "watermark", "-j", "4"]).unwrap();
+        let Command::Batch(BatchCommand::Encode { batch, output_dir, .. }) = cli.command else {
+            panic!("Expected batch encode");
+        };
+        assert_eq!(batch.inputs, ["renders", "shots/*.png"]);
+        assert_eq!(batch.jobs, Some(4));
+        assert_eq!(output_dir, "out");
+    }
+
+    #[test]
+    fn test_invalid_arguments_are_errors() {
+        assert!(Cli::try_parse_from(["steganopng", "encode", "cover.png", "rust", "message"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "encode", "cover.png", "ru", "message"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "encode", "cover.png", "éé", "message"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "encode", "cover.png", "ruSt", "message", "--cipher", "rot13"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "decode", "cover.png", "ruSt", "--method", "lsb"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "verify", "cover.png", "ruSt"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "encode", "cover.png", "ruSt", "message", "--deniable", "--key-fd", "3"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "encode", "cover.png", "ruSt", "message", "--deniable", "--cipher", "aes"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "encode", "cover.png", "ruSt", "message", "--decoy", "decoy", "--padding", "none"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "frobnicate"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "batch", "remove", "ruSt", "--output-dir", "out"]).is_err());
+        assert!(Cli::try_parse_from(["steganopng", "batch", "validate", "renders", "--signer", "spng-sig-00"]).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::cipher::Cipher;
+use crate::kdf::KdfParams;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use zeroize::Zeroizing;
+
+pub const SLOTS: usize = 2;
+const SALT_LEN: usize = 16;
+const MIN_CAPACITY: usize = 256;
+const OVERHEAD: usize = SALT_LEN + 24 + 16;
+const M_COST: u32 = 19 * 1024;
+const T_COST: u32 = 2;
+const P_COST: u32 = 1;
+
+pub fn seal(payloads: &[(&[u8], &str)]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if payloads.is_empty() || payloads.len() > SLOTS {
+        return Err(format!("A deniable payload holds between 1 and {SLOTS} messages").into());
+    }
+
+    let largest = payloads.iter().map(|(message, _)| message.len()).max().unwrap();
+    let capacity = (largest + 4).next_power_of_two().max(MIN_CAPACITY);
+    let mut rng = rand::thread_rng();
+
+    let mut slots = payloads
+        .iter()
+        .map(|(message, passphrase)| seal_slot(message, passphrase, capacity))
+        .collect::<Result<Vec<_>, _>>()?;
+    while slots.len() < SLOTS {
+        slots.push((0..capacity + OVERHEAD).map(|_| rng.gen()).collect());
+    }
+    slots.shuffle(&mut rng);
+
+    Ok(slots.concat())
+}
+
+pub fn matches(data: &[u8]) -> bool {
+    data.len().is_multiple_of(SLOTS)
+        && data.len() / SLOTS >= OVERHEAD + MIN_CAPACITY
+        && (data.len() / SLOTS - OVERHEAD).is_power_of_two()
+}
+
+pub fn open(data: &[u8], passphrase: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !matches(data) {
+        return Err("Chunk does not hold a deniable payload".into());
+    }
+
+    data.chunks(data.len() / SLOTS)
+        .find_map(|slot| open_slot(slot, passphrase))
+        .ok_or_else(|| "No message could be opened with this passphrase".into())
+}
+
+fn kdf_params(salt: [u8; SALT_LEN]) -> KdfParams {
+    KdfParams {
+        salt,
+        m_cost: M_COST,
+        t_cost: T_COST,
+        p_cost: P_COST,
+    }
+}
+
+fn seal_slot(message: &[u8], passphrase: &str, capacity: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut rng = rand::thread_rng();
+    let salt: [u8; SALT_LEN] = rng.gen();
+    let key = kdf_params(salt).derive_key(passphrase)?;
+    let nonce = Cipher::XChaCha20Poly1305.generate_nonce();
+
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(capacity));
+    plaintext.extend((message.len() as u32).to_be_bytes());
+    plaintext.extend(message);
+    plaintext.resize(capacity, 0);
+
+    let ciphertext = Cipher::XChaCha20Poly1305.encrypt(&key, &nonce, &plaintext, &[])?;
+
+    Ok(salt
+        .iter()
+        .chain(nonce.iter())
+        .chain(ciphertext.iter())
+        .copied()
+        .collect())
+}
+
+fn open_slot(slot: &[u8], passphrase: &str) -> Option<Vec<u8>> {
+    let salt: [u8; SALT_LEN] = slot[..SALT_LEN].try_into().unwrap();
+    let nonce = &slot[SALT_LEN..SALT_LEN + 24];
+    let key = kdf_params(salt).derive_key(passphrase).ok()?;
+    let plaintext = Zeroizing::new(Cipher::XChaCha20Poly1305.decrypt(&key, nonce, &slot[SALT_LEN + 24..], &[]).ok()?);
+
+    let length = u32::from_be_bytes(plaintext[..4].try_into().unwrap()) as usize;
+    plaintext.get(4..4 + length).map(<[u8]>::to_vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_passphrase_opens_its_own_message() {
+        let data = seal(&[(b"the real secret", "real passphrase"), (b"grocery list", "decoy passphrase")]).unwrap();
+        assert_eq!(open(&data, "real passphrase").unwrap(), b"the real secret");
+        assert_eq!(open(&data, "decoy passphrase").unwrap(), b"grocery list");
+        assert!(open(&data, "wrong passphrase").is_err());
+    }
+
+    #[test]
+    fn test_single_message_looks_like_two() {
+        let single = seal(&[(b"grocery list", "decoy passphrase")]).unwrap();
+        let double = seal(&[(b"the real secret", "real passphrase"), (b"grocery list", "decoy passphrase")]).unwrap();
+        assert_eq!(single.len(), double.len());
+        assert_eq!(open(&single, "decoy passphrase").unwrap(), b"grocery list");
+    }
+
+    #[test]
+    fn test_slots_are_padded_to_the_largest_message() {
+        let long = vec![7; 1000];
+        let data = seal(&[(&long, "real passphrase"), (b"short", "decoy passphrase")]).unwrap();
+        assert_eq!(data.len(), SLOTS * (1024 + OVERHEAD));
+        assert_eq!(open(&data, "real passphrase").unwrap(), long);
+    }
+
+    #[test]
+    fn test_invalid_deniable_payload() {
+        assert!(seal(&[]).is_err());
+        assert!(open(b"too short", "passphrase").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(&seal(&[(b"grocery list", "decoy passphrase")]).unwrap()));
+        assert!(!matches(b"plain text message"));
+        assert!(!matches(&[0; SLOTS * (OVERHEAD + MIN_CAPACITY + 2)]));
+    }
+}
@@ -0,0 +1,656 @@
+use std::convert::TryFrom;
+use crate::chunk_type::ChunkType;
+use crate::cipher::Cipher;
+use crate::kdf::KdfParams;
+use crate::padding::Padding;
+use crate::recipient::{Identity, Recipient};
+use crate::signature::{Signer, Verifier};
+use deoxys::{
+    aead::{KeyInit, OsRng},
+    DeoxysII256,
+};
+use generic_array::GenericArray;
+use generic_array::typenum::U32;
+use rand::Rng;
+use zeroize::{Zeroize, Zeroizing};
+
+pub enum Secret {
+    Key(GenericArray<u8, U32>),
+    Passphrase(String),
+    Recipient(Recipient),
+    Identity(Identity),
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        match self {
+            Self::Key(key) => key.as_mut_slice().zeroize(),
+            Self::Passphrase(passphrase) => passphrase.zeroize(),
+            Self::Recipient(_) | Self::Identity(_) => {},
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stanza {
+    Key {
+        nonce: [u8; 15],
+        wrapped_key: Vec<u8>,
+    },
+    Passphrase {
+        params: KdfParams,
+        nonce: [u8; 15],
+        wrapped_key: Vec<u8>,
+    },
+    X25519 {
+        ephemeral_public: [u8; 32],
+        nonce: [u8; 15],
+        wrapped_key: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub version: u8,
+    pub flags: u8,
+    pub cipher: Cipher,
+    pub padding: Padding,
+    pub nonce: Vec<u8>,
+    pub stanzas: Vec<Stanza>,
+    pub ciphertext: Vec<u8>,
+    pub signature: Option<[u8; 64]>,
+}
+
+impl TryFrom<&[u8]> for Stanza {
+    type Error = &'static str;
+
+    fn try_from(s: &[u8]) -> Result<Self, Self::Error> {
+        let mut count: usize = 0;
+        let kind = take(s, &mut count, 1)?[0];
+        let length = u16::from_be_bytes(take(s, &mut count, 2)?.try_into().unwrap()) as usize;
+        let body = take(s, &mut count, length)?;
+        if count != s.len() {
+            return Err("Trailing bytes after stanza");
+        }
+
+        let mut count: usize = 0;
+        let stanza = match kind {
+            Stanza::KEY => Stanza::Key {
+                nonce: take(body, &mut count, 15)?.try_into().unwrap(),
+                wrapped_key: take(body, &mut count, Stanza::WRAPPED_KEY_LEN)?.to_vec(),
+            },
+            Stanza::PASSPHRASE => Stanza::Passphrase {
+                params: KdfParams::try_from(take(body, &mut count, KdfParams::ENCODED_LEN)?)?,
+                nonce: take(body, &mut count, 15)?.try_into().unwrap(),
+                wrapped_key: take(body, &mut count, Stanza::WRAPPED_KEY_LEN)?.to_vec(),
+            },
+            Stanza::X25519 => Stanza::X25519 {
+                ephemeral_public: take(body, &mut count, 32)?.try_into().unwrap(),
+                nonce: take(body, &mut count, 15)?.try_into().unwrap(),
+                wrapped_key: take(body, &mut count, Stanza::WRAPPED_KEY_LEN)?.to_vec(),
+            },
+            _ => return Err("Unknown stanza type"),
+        };
+
+        if count != body.len() {
+            return Err("Stanza body has an unexpected length");
+        }
+
+        Ok(stanza)
+    }
+}
+
+impl Stanza {
+    pub const KEY: u8 = 1;
+    pub const PASSPHRASE: u8 = 2;
+    pub const X25519: u8 = 3;
+    pub const WRAPPED_KEY_LEN: usize = 48;
+
+    pub fn kind(&self) -> u8 {
+        match self {
+            Self::Key { .. } => Stanza::KEY,
+            Self::Passphrase { .. } => Stanza::PASSPHRASE,
+            Self::X25519 { .. } => Stanza::X25519,
+        }
+    }
+
+    pub fn wrap(secret: &Secret, file_key: &GenericArray<u8, U32>) -> Result<Stanza, Box<dyn std::error::Error>> {
+        let nonce: [u8; 15] = rand::thread_rng().gen();
+        match secret {
+            Secret::Key(key) => Ok(Stanza::Key {
+                nonce,
+                wrapped_key: wrap_key(key, &nonce, file_key)?,
+            }),
+            Secret::Passphrase(passphrase) => {
+                let params = KdfParams::generate();
+                let key = params.derive_key(passphrase)?;
+                Ok(Stanza::Passphrase {
+                    params,
+                    nonce,
+                    wrapped_key: wrap_key(&key, &nonce, file_key)?,
+                })
+            },
+            Secret::Recipient(recipient) => Self::wrap_to_recipient(recipient, &nonce, file_key),
+            Secret::Identity(identity) => Self::wrap_to_recipient(&identity.recipient(), &nonce, file_key),
+        }
+    }
+
+    fn wrap_to_recipient(recipient: &Recipient, nonce: &[u8; 15], file_key: &GenericArray<u8, U32>) -> Result<Stanza, Box<dyn std::error::Error>> {
+        let (ephemeral_public, key) = recipient.wrapping_key()?;
+        Ok(Stanza::X25519 {
+            ephemeral_public,
+            nonce: *nonce,
+            wrapped_key: wrap_key(&key, nonce, file_key)?,
+        })
+    }
+
+    pub fn unwrap_key(&self, secret: &Secret) -> Option<Zeroizing<GenericArray<u8, U32>>> {
+        match (self, secret) {
+            (Self::Key { nonce, wrapped_key }, Secret::Key(key)) => {
+                unwrap_key(key, nonce, wrapped_key)
+            },
+            (Self::Passphrase { params, nonce, wrapped_key }, Secret::Passphrase(passphrase)) => {
+                let key = params.derive_key(passphrase).ok()?;
+                unwrap_key(&key, nonce, wrapped_key)
+            },
+            (Self::X25519 { ephemeral_public, nonce, wrapped_key }, Secret::Identity(identity)) => {
+                let key = identity.wrapping_key(ephemeral_public)?;
+                unwrap_key(&key, nonce, wrapped_key)
+            },
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let body: Vec<u8> = match self {
+            Self::Key { nonce, wrapped_key } => nonce
+                .iter()
+                .chain(wrapped_key.iter())
+                .copied()
+                .collect(),
+            Self::Passphrase { params, nonce, wrapped_key } => params
+                .as_bytes()
+                .iter()
+                .chain(nonce.iter())
+                .chain(wrapped_key.iter())
+                .copied()
+                .collect(),
+            Self::X25519 { ephemeral_public, nonce, wrapped_key } => ephemeral_public
+                .iter()
+                .chain(nonce.iter())
+                .chain(wrapped_key.iter())
+                .copied()
+                .collect(),
+        };
+
+        [self.kind()]
+            .iter()
+            .chain((body.len() as u16).to_be_bytes().iter())
+            .chain(body.iter())
+            .copied()
+            .collect()
+    }
+}
+
+impl TryFrom<&[u8]> for Envelope {
+    type Error = &'static str;
+
+    fn try_from(e: &[u8]) -> Result<Self, Self::Error> {
+        let mut count: usize = 0;
+
+        if take(e, &mut count, 4)? != Envelope::MAGIC {
+            return Err("Not a steganopng envelope");
+        }
+
+        let version = take(e, &mut count, 1)?[0];
+        if version != Envelope::VERSION {
+            return Err("Unsupported envelope version");
+        }
+
+        let flags = take(e, &mut count, 1)?[0];
+        if flags & !(Envelope::FLAG_SIGNED | Envelope::FLAG_BINARY | Envelope::FLAG_BOUND | Envelope::FLAG_PADDED) != 0 {
+            return Err("Unsupported envelope flags");
+        }
+
+        let cipher = Cipher::try_from(take(e, &mut count, 1)?[0])?;
+
+        let padding = if flags & Envelope::FLAG_PADDED != 0 {
+            Padding::try_from(take(e, &mut count, 1)?[0])?
+        } else {
+            Padding::None
+        };
+
+        let nonce_length = take(e, &mut count, 1)?[0] as usize;
+        if nonce_length != cipher.nonce_len() {
+            return Err("Invalid nonce length");
+        }
+        let nonce = take(e, &mut count, nonce_length)?.to_vec();
+
+        let stanza_count = take(e, &mut count, 1)?[0];
+        let mut stanzas = Vec::new();
+        for _ in 0..stanza_count {
+            let start = count;
+            take(e, &mut count, 1)?;
+            let length = u16::from_be_bytes(take(e, &mut count, 2)?.try_into().unwrap()) as usize;
+            take(e, &mut count, length)?;
+            stanzas.push(Stanza::try_from(&e[start..count])?);
+        }
+
+        if stanzas.is_empty() {
+            return Err("Envelope has no key stanzas");
+        }
+
+        let (ciphertext, signature) = if flags & Envelope::FLAG_SIGNED != 0 {
+            if e.len() < count + 64 {
+                return Err("Envelope is truncated");
+            }
+            let (ciphertext, signature) = e[count..].split_at(e.len() - count - 64);
+            (ciphertext.to_vec(), Some(signature.try_into().unwrap()))
+        } else {
+            (e[count..].to_vec(), None)
+        };
+
+        Ok(Self {
+            version,
+            flags,
+            cipher,
+            padding,
+            nonce,
+            stanzas,
+            ciphertext,
+            signature,
+        })
+    }
+}
+
+impl Envelope {
+    pub const MAGIC: [u8; 4] = *b"SPNG";
+    pub const VERSION: u8 = 1;
+    pub const FLAG_SIGNED: u8 = 0b0000_0001;
+    pub const FLAG_BINARY: u8 = 0b0000_0010;
+    pub const FLAG_BOUND: u8 = 0b0000_0100;
+    pub const FLAG_PADDED: u8 = 0b0000_1000;
+    pub const MAX_PASSPHRASE_STANZAS: usize = 4;
+
+    pub fn seal(plaintext: &[u8], secrets: &[Secret], cipher: Cipher, padding: Padding, signer: Option<(&Signer, &ChunkType)>, carrier: Option<&[u8]>) -> Result<Envelope, Box<dyn std::error::Error>> {
+        if secrets.is_empty() || secrets.len() > u8::MAX as usize {
+            return Err("An envelope needs between 1 and 255 keys".into());
+        }
+        if secrets.iter().filter(|secret| matches!(secret, Secret::Passphrase(_))).count() > Envelope::MAX_PASSPHRASE_STANZAS {
+            return Err(format!("An envelope holds at most {} passphrases", Envelope::MAX_PASSPHRASE_STANZAS).into());
+        }
+
+        let file_key = Zeroizing::new(DeoxysII256::generate_key(&mut OsRng));
+        let mut envelope = Envelope {
+            version: Envelope::VERSION,
+            flags: Envelope::FLAG_BINARY
+                | if signer.is_some() { Envelope::FLAG_SIGNED } else { 0 }
+                | if carrier.is_some() { Envelope::FLAG_BOUND } else { 0 }
+                | if padding != Padding::None { Envelope::FLAG_PADDED } else { 0 },
+            cipher,
+            padding,
+            nonce: cipher.generate_nonce(),
+            stanzas: secrets
+                .iter()
+                .map(|secret| Stanza::wrap(secret, &file_key))
+                .collect::<Result<_, _>>()?,
+            ciphertext: Vec::new(),
+            signature: None,
+        };
+
+        let aad = envelope.associated_data(carrier.unwrap_or_default());
+        envelope.ciphertext = cipher.encrypt(&file_key, &envelope.nonce, &padding.pad(plaintext), &aad)?;
+
+        if let Some((signer, chunk_type)) = signer {
+            envelope.signature = Some(signer.sign(&envelope.signed_message(chunk_type)));
+        }
+
+        Ok(envelope)
+    }
+
+    pub fn open(&self, secrets: &[Secret], carrier: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let file_key = self.stanzas
+            .iter()
+            .filter(|stanza| stanza.kind() != Stanza::PASSPHRASE)
+            .chain(self.stanzas.iter().filter(|stanza| stanza.kind() == Stanza::PASSPHRASE).take(Envelope::MAX_PASSPHRASE_STANZAS))
+            .find_map(|stanza| secrets.iter().find_map(|secret| stanza.unwrap_key(secret)));
+        let Some(file_key) = file_key else {
+            return Err("No key stanza could be opened with the supplied secret".into());
+        };
+
+        let aad = self.associated_data(carrier);
+        let plaintext = self.cipher.decrypt(&file_key, &self.nonce, &self.ciphertext, &aad).map_err(|e| {
+            if self.is_bound() {
+                "Payload failed authentication, it was moved to another image or the image data was re-encoded"
+            } else {
+                e
+            }
+        })?;
+
+        Ok(self.padding.unpad(&plaintext)?)
+    }
+
+    pub fn is_bound(&self) -> bool {
+        self.flags & Envelope::FLAG_BOUND != 0
+    }
+
+    fn associated_data(&self, carrier: &[u8]) -> Vec<u8> {
+        let mut aad = self.header();
+        if self.is_bound() {
+            aad.extend(carrier);
+        }
+        aad
+    }
+
+    pub fn from_chunk_data(data: &[u8]) -> Result<Option<Envelope>, &'static str> {
+        let (envelope, binary) = if data.starts_with(&Envelope::MAGIC) {
+            (Envelope::try_from(data)?, true)
+        } else {
+            match hex::decode(data) {
+                Ok(bytes) if bytes.starts_with(&Envelope::MAGIC) => (Envelope::try_from(bytes.as_ref())?, false),
+                _ => return Ok(None),
+            }
+        };
+
+        if binary != (envelope.flags & Envelope::FLAG_BINARY != 0) {
+            return Err("Envelope encoding does not match its flags");
+        }
+
+        Ok(Some(envelope))
+    }
+
+    pub fn to_chunk_data(&self) -> Vec<u8> {
+        if self.flags & Envelope::FLAG_BINARY != 0 {
+            self.as_bytes()
+        } else {
+            hex::encode(self.as_bytes()).into_bytes()
+        }
+    }
+
+    pub fn verify(&self, verifier: &Verifier, chunk_type: &ChunkType) -> Result<(), &'static str> {
+        let Some(signature) = self.signature else {
+            return Err("Payload is not signed");
+        };
+        if !verifier.verify(&self.signed_message(chunk_type), &signature) {
+            return Err("Bad signature");
+        }
+        Ok(())
+    }
+
+    fn signed_message(&self, chunk_type: &ChunkType) -> Vec<u8> {
+        b"steganopng signature"
+            .iter()
+            .chain(chunk_type.bytes().iter())
+            .chain(self.header().iter())
+            .chain(self.ciphertext.iter())
+            .copied()
+            .collect()
+    }
+
+    pub fn has_stanza(&self, kind: u8) -> bool {
+        self.stanzas.iter().any(|stanza| stanza.kind() == kind)
+    }
+
+    pub fn header(&self) -> Vec<u8> {
+        let stanza_bytes: Vec<u8> = self.stanzas
+            .iter()
+            .flat_map(|stanza| stanza.as_bytes())
+            .collect();
+
+        let padding = (self.flags & Envelope::FLAG_PADDED != 0).then(|| self.padding.id());
+
+        Envelope::MAGIC
+            .iter()
+            .chain([self.version, self.flags, self.cipher.id()].iter())
+            .chain(padding.iter())
+            .chain([self.nonce.len() as u8].iter())
+            .chain(self.nonce.iter())
+            .chain([self.stanzas.len() as u8].iter())
+            .chain(stanza_bytes.iter())
+            .copied()
+            .collect()
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.header()
+            .iter()
+            .chain(self.ciphertext.iter())
+            .chain(self.signature.iter().flatten())
+            .copied()
+            .collect()
+    }
+}
+
+fn take<'a>(b: &'a [u8], count: &mut usize, length: usize) -> Result<&'a [u8], &'static str> {
+    let Some(slice) = b.get(*count..*count + length) else {
+        return Err("Envelope is truncated");
+    };
+    *count += length;
+    Ok(slice)
+}
+
+fn wrap_key(key: &GenericArray<u8, U32>, nonce: &[u8; 15], file_key: &GenericArray<u8, U32>) -> Result<Vec<u8>, &'static str> {
+    Cipher::DeoxysII256.encrypt(key, nonce, file_key, &[])
+}
+
+fn unwrap_key(key: &GenericArray<u8, U32>, nonce: &[u8; 15], wrapped_key: &[u8]) -> Option<Zeroizing<GenericArray<u8, U32>>> {
+    let file_key = Zeroizing::new(Cipher::DeoxysII256.decrypt(key, nonce, wrapped_key, &[]).ok()?);
+    Some(Zeroizing::new(*GenericArray::from_slice(&file_key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use super::*;
+
+    fn testing_key() -> GenericArray<u8, U32> {
+        *GenericArray::from_slice(&[42; 32])
+    }
+
+    #[test]
+    fn test_seal_and_open_with_key() {
+        let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        let plaintext = envelope.open(&[Secret::Key(testing_key())], &[]).unwrap();
+        assert_eq!(plaintext, b"secret message");
+        assert!(!envelope.has_stanza(Stanza::PASSPHRASE));
+    }
+
+    #[test]
+    fn test_seal_and_open_with_passphrase() {
+        let envelope = Envelope::seal(b"secret message", &[Secret::Passphrase(String::from("hunter2"))], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        let plaintext = envelope.open(&[Secret::Passphrase(String::from("hunter2"))], &[]).unwrap();
+        assert_eq!(plaintext, b"secret message");
+        assert!(envelope.has_stanza(Stanza::PASSPHRASE));
+    }
+
+    #[test]
+    fn test_seal_and_open_with_recipient() {
+        let identity = Identity::generate();
+        let envelope = Envelope::seal(b"secret message", &[Secret::Recipient(identity.recipient())], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        assert!(envelope.has_stanza(Stanza::X25519));
+
+        let parsed = Envelope::try_from(envelope.as_bytes().as_ref()).unwrap();
+        assert_eq!(parsed.open(&[Secret::Identity(identity)], &[]).unwrap(), b"secret message");
+        assert!(parsed.open(&[Secret::Identity(Identity::generate())], &[]).is_err());
+    }
+
+    #[test]
+    fn test_seal_to_several_recipients() {
+        let alice = Identity::generate();
+        let bob = Identity::generate();
+        let secrets = [
+            Secret::Recipient(alice.recipient()),
+            Secret::Passphrase(String::from("hunter2")),
+            Secret::Recipient(bob.recipient()),
+            Secret::Key(testing_key()),
+        ];
+        let envelope = Envelope::seal(b"secret message", &secrets, Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        let parsed = Envelope::try_from(envelope.as_bytes().as_ref()).unwrap();
+        assert_eq!(parsed.stanzas.len(), 4);
+
+        assert_eq!(parsed.open(&[Secret::Identity(alice)], &[]).unwrap(), b"secret message");
+        assert_eq!(parsed.open(&[Secret::Identity(Identity::generate()), Secret::Identity(bob)], &[]).unwrap(), b"secret message");
+        assert_eq!(parsed.open(&[Secret::Passphrase(String::from("hunter2"))], &[]).unwrap(), b"secret message");
+        assert_eq!(parsed.open(&[Secret::Key(testing_key())], &[]).unwrap(), b"secret message");
+        assert!(parsed.open(&[Secret::Identity(Identity::generate())], &[]).is_err());
+    }
+
+    #[test]
+    fn test_seal_with_each_cipher() {
+        for cipher in [Cipher::DeoxysII256, Cipher::Aes256GcmSiv, Cipher::XChaCha20Poly1305] {
+            let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], cipher, Padding::None, None, None).unwrap();
+            let parsed = Envelope::try_from(envelope.as_bytes().as_ref()).unwrap();
+            assert_eq!(parsed.cipher, cipher);
+            assert_eq!(parsed.nonce.len(), cipher.nonce_len());
+            assert_eq!(parsed.open(&[Secret::Key(testing_key())], &[]).unwrap(), b"secret message");
+        }
+    }
+
+    #[test]
+    fn test_chunk_data_is_binary() {
+        let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        let data = envelope.to_chunk_data();
+        assert_eq!(data, envelope.as_bytes());
+        assert_eq!(Envelope::from_chunk_data(&data).unwrap().unwrap(), envelope);
+    }
+
+    #[test]
+    fn test_hex_chunk_data() {
+        let mut envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        envelope.flags &= !Envelope::FLAG_BINARY;
+        let data = envelope.to_chunk_data();
+        assert_eq!(data, hex::encode(envelope.as_bytes()).into_bytes());
+        assert_eq!(Envelope::from_chunk_data(&data).unwrap().unwrap(), envelope);
+
+        let mismatched = hex::encode(Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, None).unwrap().as_bytes());
+        assert!(Envelope::from_chunk_data(mismatched.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_legacy_chunk_data() {
+        assert!(Envelope::from_chunk_data(b"deadbeef").unwrap().is_none());
+        assert!(Envelope::from_chunk_data(b"plain text message").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_bound_envelope() {
+        let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, Some(b"carrier")).unwrap();
+        let parsed = Envelope::try_from(envelope.as_bytes().as_ref()).unwrap();
+        assert!(parsed.is_bound());
+        assert_eq!(parsed.open(&[Secret::Key(testing_key())], b"carrier").unwrap(), b"secret message");
+
+        let error = parsed.open(&[Secret::Key(testing_key())], b"another carrier").unwrap_err();
+        assert!(error.to_string().contains("another image"));
+    }
+
+    #[test]
+    fn test_unbound_envelope_ignores_carrier() {
+        let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        assert!(!envelope.is_bound());
+        assert_eq!(envelope.open(&[Secret::Key(testing_key())], b"anything").unwrap(), b"secret message");
+    }
+
+    #[test]
+    fn test_signed_envelope() {
+        let signer = Signer::generate();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, Some((&signer, &chunk_type)), None).unwrap();
+        let bytes = envelope.as_bytes();
+
+        let parsed = Envelope::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(parsed, envelope);
+        assert!(parsed.verify(&signer.verifier(), &chunk_type).is_ok());
+        assert!(parsed.verify(&Signer::generate().verifier(), &chunk_type).is_err());
+        assert!(parsed.verify(&signer.verifier(), &ChunkType::from_str("ruSx").unwrap()).is_err());
+        assert_eq!(parsed.open(&[Secret::Key(testing_key())], &[]).unwrap(), b"secret message");
+
+        let mut tampered = bytes.clone();
+        let index = tampered.len() - 70;
+        tampered[index] ^= 1;
+        let tampered = Envelope::try_from(tampered.as_ref()).unwrap();
+        assert!(tampered.verify(&signer.verifier(), &chunk_type).is_err());
+    }
+
+    #[test]
+    fn test_unsigned_envelope_does_not_verify() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        assert!(envelope.signature.is_none());
+        assert!(envelope.verify(&Signer::generate().verifier(), &chunk_type).is_err());
+    }
+
+    #[test]
+    fn test_open_with_wrong_secret() {
+        let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        let wrong_key = *GenericArray::from_slice(&[7; 32]);
+        assert!(envelope.open(&[Secret::Key(wrong_key)], &[]).is_err());
+        assert!(envelope.open(&[Secret::Passphrase(String::from("hunter2"))], &[]).is_err());
+    }
+
+    #[test]
+    fn test_passphrase_stanzas_are_capped() {
+        let mut envelope = Envelope::seal(b"secret message", &[Secret::Passphrase(String::from("hunter2"))], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        let decoy = Stanza::Passphrase {
+            params: KdfParams { m_cost: 64, t_cost: 1, p_cost: 1, ..KdfParams::generate() },
+            nonce: [0; 15],
+            wrapped_key: vec![0; Stanza::WRAPPED_KEY_LEN],
+        };
+        let secrets = [Secret::Passphrase(String::from("hunter2"))];
+
+        envelope.stanzas.splice(0..0, vec![decoy.clone(); Envelope::MAX_PASSPHRASE_STANZAS - 1]);
+        let error = envelope.open(&secrets, &[]).unwrap_err().to_string();
+        assert!(!error.starts_with("No key stanza"), "{error}");
+
+        envelope.stanzas.insert(0, decoy);
+        let error = envelope.open(&secrets, &[]).unwrap_err().to_string();
+        assert!(error.starts_with("No key stanza"), "{error}");
+    }
+
+    #[test]
+    fn test_seal_rejects_too_many_passphrases() {
+        let secrets: Vec<Secret> = (0..=Envelope::MAX_PASSPHRASE_STANZAS).map(|n| Secret::Passphrase(format!("passphrase {n}"))).collect();
+        assert!(Envelope::seal(b"secret message", &secrets, Cipher::DeoxysII256, Padding::None, None, None).is_err());
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        let bytes = envelope.as_bytes();
+        assert_eq!(&bytes[..4], b"SPNG");
+
+        let parsed = Envelope::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(parsed, envelope);
+        assert_eq!(parsed.open(&[Secret::Key(testing_key())], &[]).unwrap(), b"secret message");
+    }
+
+    #[test]
+    fn test_padded_envelope_hides_length() {
+        let short = Envelope::seal(b"yes", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::Buckets, None, None).unwrap();
+        let long = Envelope::seal(&[b'x'; 200], &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::Buckets, None, None).unwrap();
+        assert_eq!(short.ciphertext.len(), long.ciphertext.len());
+
+        let parsed = Envelope::try_from(short.as_bytes().as_ref()).unwrap();
+        assert_eq!(parsed.padding, Padding::Buckets);
+        assert_ne!(parsed.flags & Envelope::FLAG_PADDED, 0);
+        assert_eq!(parsed.open(&[Secret::Key(testing_key())], &[]).unwrap(), b"yes");
+    }
+
+    #[test]
+    fn test_tampered_header_fails() {
+        let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        let mut bytes = envelope.as_bytes();
+        bytes[8] ^= 1;
+        let parsed = Envelope::try_from(bytes.as_ref()).unwrap();
+        assert!(parsed.open(&[Secret::Key(testing_key())], &[]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_envelope() {
+        assert!(Envelope::try_from(&b"SPNX"[..]).is_err());
+        assert!(Envelope::try_from(&b"SPNG\x01"[..]).is_err());
+
+        let envelope = Envelope::seal(b"secret message", &[Secret::Key(testing_key())], Cipher::DeoxysII256, Padding::None, None, None).unwrap();
+        let bytes = envelope.as_bytes();
+        assert!(Envelope::try_from(&bytes[..40]).is_err());
+    }
+}
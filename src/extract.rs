@@ -0,0 +1,198 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use flate2::read::ZlibDecoder;
+use serde::Serialize;
+use crate::attachment::Attachment;
+use crate::chunk_type::ChunkType;
+use crate::envelope::Envelope;
+use crate::listing;
+use crate::png::Png;
+use crate::shamir::Share;
+
+pub const MANIFEST: &str = "manifest.json";
+const MAX_INFLATED_LEN: u64 = 64 * 1024 * 1024;
+const STANDARD_TYPES: [&str; 33] = [
+    "IHDR", "PLTE", "IDAT", "IEND", "tRNS", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCV",
+    "cLLI", "iTXt", "tEXt", "zTXt", "bKGD", "hIST", "pHYs", "sPLT", "eXIf", "tIME", "acTL", "fcTL",
+    "fdAT", "oFFs", "pCAL", "sCAL", "sTER", "gIFg", "gIFx", "fRAc", "dSIG",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Extracted {
+    pub index: usize,
+    pub offset: usize,
+    pub chunk_type: String,
+    pub length: u32,
+    pub crc_valid: bool,
+    pub file: String,
+    pub utf8: bool,
+    pub zlib: bool,
+    pub inflated_file: Option<String>,
+    pub payload: Option<&'static str>,
+}
+
+pub fn is_standard(chunk_type: &ChunkType) -> bool {
+    chunk_type.is_public() && STANDARD_TYPES.contains(&chunk_type.to_string().as_str())
+}
+
+pub fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let header = data.get(..2)?;
+    if header[0] & 0x0f != 8 || u16::from_be_bytes([header[0], header[1]]) % 31 != 0 {
+        return None;
+    }
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(data)
+        .take(MAX_INFLATED_LEN + 1)
+        .read_to_end(&mut inflated)
+        .ok()?;
+    (inflated.len() as u64 <= MAX_INFLATED_LEN).then_some(inflated)
+}
+
+pub fn payload_kind(data: &[u8]) -> Option<&'static str> {
+    if Attachment::matches(data) {
+        Some("file")
+    } else if Share::matches(data) {
+        Some("share")
+    } else if matches!(Envelope::from_chunk_data(data), Ok(Some(_))) {
+        Some("envelope")
+    } else {
+        None
+    }
+}
+
+pub fn extract_all(png: &Png, dir: &Path) -> Result<Vec<Extracted>, Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {e}", dir.display()))?;
+
+    let mut extracted = Vec::new();
+    for (entry, chunk) in listing::entries(png).into_iter().zip(png.chunks()) {
+        if is_standard(chunk.chunk_type()) {
+            continue;
+        }
+        let name = format!("{:03}-{}", entry.index, entry.chunk_type);
+        let utf8 = std::str::from_utf8(chunk.data()).is_ok();
+        let file = format!("{name}.{}", extension(utf8));
+        write_new(&dir.join(&file), chunk.data())?;
+
+        let inflated = inflate(chunk.data());
+        let inflated_file = match &inflated {
+            Some(inflated) => {
+                let file = format!("{name}.inflated.{}", extension(std::str::from_utf8(inflated).is_ok()));
+                write_new(&dir.join(&file), inflated)?;
+                Some(file)
+            },
+            None => None,
+        };
+
+        extracted.push(Extracted {
+            index: entry.index,
+            offset: entry.offset,
+            chunk_type: entry.chunk_type,
+            length: entry.length,
+            crc_valid: entry.crc_valid,
+            file,
+            utf8,
+            zlib: inflated.is_some(),
+            inflated_file,
+            payload: payload_kind(chunk.data()),
+        });
+    }
+
+    write_new(&dir.join(MANIFEST), format!("{}\n", serde_json::to_string_pretty(&extracted)?).as_bytes())?;
+    Ok(extracted)
+}
+
+fn extension(utf8: bool) -> &'static str {
+    if utf8 { "txt" } else { "bin" }
+}
+
+fn write_new(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(bytes))
+        .map_err(|e| format!("Cannot create {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use crate::testing::{chunk, TempDir};
+
+    fn compressed(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", b"header data"),
+            chunk("tEXt", b"Comment\0hello"),
+            chunk("ruSt", b"hidden message"),
+            chunk("prIv", &compressed(b"compressed message")),
+            chunk("IDAT", &compressed(b"pixels")),
+            chunk("blOB", &[0xff, 0xfe, 0x00]),
+            chunk("IEND", b""),
+        ])
+    }
+
+    #[test]
+    fn test_is_standard() {
+        assert!(is_standard(&ChunkType::from_str("IHDR").unwrap()));
+        assert!(is_standard(&ChunkType::from_str("tEXt").unwrap()));
+        assert!(!is_standard(&ChunkType::from_str("ruSt").unwrap()));
+        assert!(!is_standard(&ChunkType::from_str("iHDR").unwrap()));
+    }
+
+    #[test]
+    fn test_inflate() {
+        assert_eq!(inflate(&compressed(b"message")).unwrap(), b"message");
+        assert!(inflate(b"message").is_none());
+        assert!(inflate(&compressed(b"message")[..6]).is_none());
+        assert!(inflate(&compressed(&vec![0; MAX_INFLATED_LEN as usize + 1])).is_none());
+    }
+
+    #[test]
+    fn test_extract_all() {
+        let dir = TempDir::new("extract");
+        let dir = dir.join("out");
+
+        let extracted = extract_all(&testing_png(), &dir).unwrap();
+        let types: Vec<&str> = extracted.iter().map(|entry| entry.chunk_type.as_str()).collect();
+        assert_eq!(types, ["ruSt", "prIv", "blOB"]);
+
+        assert_eq!(extracted[0].file, "002-ruSt.txt");
+        assert_eq!(fs::read(dir.join("002-ruSt.txt")).unwrap(), b"hidden message");
+        assert!(extracted[0].utf8 && !extracted[0].zlib);
+
+        assert_eq!(extracted[1].file, "003-prIv.bin");
+        assert_eq!(extracted[1].inflated_file.as_deref(), Some("003-prIv.inflated.txt"));
+        assert_eq!(fs::read(dir.join("003-prIv.inflated.txt")).unwrap(), b"compressed message");
+
+        assert_eq!(extracted[2].file, "005-blOB.bin");
+        assert!(!extracted[2].utf8 && !extracted[2].zlib);
+
+        let manifest: serde_json::Value = serde_json::from_slice(&fs::read(dir.join(MANIFEST)).unwrap()).unwrap();
+        assert_eq!(manifest.as_array().unwrap().len(), 3);
+        assert_eq!(manifest[1]["zlib"], true);
+        assert_eq!(manifest[0]["offset"], extracted[0].offset);
+
+        assert!(extract_all(&testing_png(), &dir).is_err());
+    }
+
+    #[test]
+    fn test_payload_kind() {
+        let attachment = Attachment {
+            name: String::from("notes.txt"),
+            mime: String::from("text/plain"),
+            data: b"notes".to_vec(),
+        };
+        assert_eq!(payload_kind(&attachment.as_bytes()), Some("file"));
+        assert_eq!(payload_kind(b"plain message"), None);
+    }
+}
@@ -0,0 +1,123 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use generic_array::GenericArray;
+use generic_array::typenum::U32;
+use rand::Rng;
+use zeroize::Zeroizing;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+    pub salt: [u8; 16],
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl TryFrom<&[u8]> for KdfParams {
+    type Error = &'static str;
+
+    fn try_from(b: &[u8]) -> Result<Self, Self::Error> {
+        if b.len() != KdfParams::ENCODED_LEN {
+            return Err("Argon2id parameters should be 28 bytes long");
+        }
+
+        Self {
+            salt: b[..16].try_into().unwrap(),
+            m_cost: u32::from_be_bytes(b[16..20].try_into().unwrap()),
+            t_cost: u32::from_be_bytes(b[20..24].try_into().unwrap()),
+            p_cost: u32::from_be_bytes(b[24..28].try_into().unwrap()),
+        }
+        .checked()
+    }
+}
+
+impl KdfParams {
+    pub const ENCODED_LEN: usize = 28;
+    pub const MAX_M_COST: u32 = 4 * Params::DEFAULT_M_COST;
+    pub const MAX_T_COST: u32 = 4 * Params::DEFAULT_T_COST;
+    pub const MAX_P_COST: u32 = 4 * Params::DEFAULT_P_COST;
+
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        Self {
+            salt: rng.gen(),
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+
+    fn checked(self) -> Result<Self, &'static str> {
+        if self.m_cost > KdfParams::MAX_M_COST || self.t_cost > KdfParams::MAX_T_COST || self.p_cost > KdfParams::MAX_P_COST {
+            return Err("Argon2id cost parameters are too high");
+        }
+        Ok(self)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.salt
+            .iter()
+            .chain(self.m_cost.to_be_bytes().iter())
+            .chain(self.t_cost.to_be_bytes().iter())
+            .chain(self.p_cost.to_be_bytes().iter())
+            .copied()
+            .collect()
+    }
+
+    pub fn derive_key(&self, passphrase: &str) -> Result<Zeroizing<GenericArray<u8, U32>>, Box<dyn std::error::Error>> {
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))
+            .map_err(|e| format!("Invalid Argon2id parameters: {e}"))?;
+        let mut key = Zeroizing::new(GenericArray::default());
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &self.salt, &mut key)
+            .map_err(|e| format!("Key derivation failed: {e}"))?;
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_params() -> KdfParams {
+        KdfParams {
+            salt: [7; 16],
+            m_cost: 64,
+            t_cost: 1,
+            p_cost: 1,
+        }
+    }
+
+    #[test]
+    fn test_params_bytes_round_trip() {
+        let params = testing_params();
+        let bytes = params.as_bytes();
+        assert_eq!(bytes.len(), KdfParams::ENCODED_LEN);
+        assert_eq!(KdfParams::try_from(bytes.as_ref()).unwrap(), params);
+        assert!(KdfParams::try_from(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn test_excessive_costs_are_rejected() {
+        for (m_cost, t_cost, p_cost) in [(0x4000_0000, 1, 1), (64, 1000, 1), (64, 1, 255)] {
+            let params = KdfParams { m_cost, t_cost, p_cost, ..testing_params() };
+            assert!(KdfParams::try_from(params.as_bytes().as_ref()).is_err());
+        }
+        let generated = KdfParams::generate();
+        assert_eq!(KdfParams::try_from(generated.as_bytes().as_ref()).unwrap(), generated);
+    }
+
+    #[test]
+    fn test_derive_key_is_deterministic() {
+        let params = testing_params();
+        let first = params.derive_key("correct horse battery staple").unwrap();
+        let second = params.derive_key("correct horse battery staple").unwrap();
+        let other = params.derive_key("Tr0ub4dor&3").unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_generate_uses_fresh_salt() {
+        assert_ne!(KdfParams::generate().salt, KdfParams::generate().salt);
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use crate::envelope::Secret;
+use crate::recipient::Identity;
+use crate::signature::Signer;
+use deoxys::{
+    aead::{KeyInit, OsRng},
+    DeoxysII256,
+};
+use generic_array::GenericArray;
+use generic_array::typenum::U32;
+use zeroize::Zeroizing;
+
+#[derive(Clone)]
+pub enum KeyFile {
+    Symmetric(GenericArray<u8, U32>),
+    Identity(Identity),
+    Signing(Signer),
+}
+
+impl FromStr for KeyFile {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(line) = s.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with('#')) else {
+            return Err("No key found");
+        };
+
+        if let Some(key) = line.strip_prefix(KeyFile::SYMMETRIC_PREFIX) {
+            let key = Zeroizing::new(hex::decode(key).map_err(|_| "Key is not valid hex")?);
+            if key.len() != 32 {
+                return Err("Key must be 32 bytes");
+            }
+            Ok(Self::Symmetric(*GenericArray::from_slice(&key)))
+        } else if line.starts_with(Identity::PREFIX) {
+            Ok(Self::Identity(Identity::from_str(line)?))
+        } else if line.starts_with(Signer::PREFIX) {
+            Ok(Self::Signing(Signer::from_str(line)?))
+        } else {
+            Err("Unrecognised key file")
+        }
+    }
+}
+
+impl fmt::Display for KeyFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Symmetric(key) => write!(f, "{}{}", KeyFile::SYMMETRIC_PREFIX, hex::encode(key)),
+            Self::Identity(identity) => write!(f, "{identity}"),
+            Self::Signing(signer) => write!(f, "{signer}"),
+        }
+    }
+}
+
+impl KeyFile {
+    pub const SYMMETRIC_PREFIX: &'static str = "SPNG-KEY-";
+
+    pub fn generate(kind: &str) -> Result<Self, &'static str> {
+        match kind {
+            "symmetric" => Ok(Self::Symmetric(DeoxysII256::generate_key(&mut OsRng))),
+            "x25519" => Ok(Self::Identity(Identity::generate())),
+            "ed25519" => Ok(Self::Signing(Signer::generate())),
+            _ => Err("Unknown key type, expected symmetric, x25519 or ed25519"),
+        }
+    }
+
+    pub fn read(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = Zeroizing::new(fs::read_to_string(path).map_err(|e| format!("Cannot read key file {path}: {e}"))?);
+        Ok(Self::from_str(&contents)?)
+    }
+
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        let public_path = format!("{path}.pub");
+        let public_file = match self.public_key() {
+            Some(public_key) => Some((
+                public_key,
+                OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&public_path)?,
+            )),
+            None => None,
+        };
+        if let Err(e) = write_private(Path::new(path), format!("{self}\n").as_bytes()) {
+            if public_file.is_some() {
+                let _ = fs::remove_file(&public_path);
+            }
+            return Err(e);
+        }
+        if let Some((public_key, mut file)) = public_file {
+            writeln!(file, "{public_key}")?;
+        }
+        Ok(())
+    }
+
+    pub fn public_key(&self) -> Option<String> {
+        match self {
+            Self::Symmetric(_) => None,
+            Self::Identity(identity) => Some(identity.recipient().to_string()),
+            Self::Signing(signer) => Some(signer.verifier().to_string()),
+        }
+    }
+
+    pub fn secret(&self) -> Result<Secret, &'static str> {
+        match self {
+            Self::Symmetric(key) => Ok(Secret::Key(*key)),
+            Self::Identity(identity) => Ok(Secret::Identity(identity.clone())),
+            Self::Signing(_) => Err("Signing keys cannot encrypt or decrypt payloads"),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents)
+}
+
+#[cfg(not(unix))]
+pub fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    file.write_all(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_key_file_round_trip() {
+        for kind in ["symmetric", "x25519", "ed25519"] {
+            let key = KeyFile::generate(kind).unwrap();
+            let parsed = KeyFile::from_str(&key.to_string()).unwrap();
+            assert_eq!(parsed.to_string(), key.to_string());
+            assert_eq!(parsed.public_key(), key.public_key());
+        }
+        assert!(KeyFile::generate("rsa").is_err());
+    }
+
+    #[test]
+    fn test_invalid_key_file() {
+        assert!(KeyFile::from_str("# nothing here").is_err());
+        assert!(KeyFile::from_str("SPNG-KEY-abcd").is_err());
+        assert!(KeyFile::from_str("deadbeef").is_err());
+    }
+
+    #[test]
+    fn test_key_file_secret() {
+        assert!(matches!(KeyFile::generate("symmetric").unwrap().secret(), Ok(Secret::Key(_))));
+        assert!(matches!(KeyFile::generate("x25519").unwrap().secret(), Ok(Secret::Identity(_))));
+        assert!(KeyFile::generate("ed25519").unwrap().secret().is_err());
+    }
+
+    #[test]
+    fn test_write_and_read_key_file() {
+        let dir = TempDir::new("keyfile-write");
+        let path = dir.path("identity.key");
+        let path = path.as_str();
+
+        let key = KeyFile::generate("x25519").unwrap();
+        key.write(path).unwrap();
+        assert!(key.write(path).is_err());
+
+        let read = KeyFile::read(path).unwrap();
+        assert_eq!(read.to_string(), key.to_string());
+        let public_key = fs::read_to_string(format!("{path}.pub")).unwrap();
+        assert_eq!(public_key.trim(), key.public_key().unwrap());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_write_leaves_no_orphans() {
+        let dir = TempDir::new("keyfile-orphans");
+        let taken_public = dir.join("public-taken.key");
+        fs::write(dir.join("public-taken.key.pub"), "existing").unwrap();
+        assert!(KeyFile::generate("ed25519").unwrap().write(taken_public.to_str().unwrap()).is_err());
+        assert!(!taken_public.exists());
+
+        let taken_private = dir.join("private-taken.key");
+        fs::write(&taken_private, "existing").unwrap();
+        assert!(KeyFile::generate("x25519").unwrap().write(taken_private.to_str().unwrap()).is_err());
+        assert!(!dir.join("private-taken.key.pub").exists());
+        assert_eq!(fs::read_to_string(&taken_private).unwrap(), "existing");
+    }
+}
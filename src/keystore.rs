@@ -0,0 +1,188 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::cipher::Cipher;
+use crate::envelope::{Envelope, Secret};
+use crate::keyfile::{self, KeyFile};
+use crate::padding::Padding;
+use crate::secret_input;
+use zeroize::Zeroizing;
+
+pub const PATH_VAR: &str = "STEGANOPNG_KEYSTORE";
+pub const PASSPHRASE_VAR: &str = "STEGANOPNG_KEYSTORE_PASSPHRASE";
+
+#[derive(Clone, Default)]
+pub struct Keystore {
+    entries: Vec<(String, KeyFile)>,
+}
+
+impl FromStr for Keystore {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keystore = Keystore::default();
+        for line in s.lines().filter(|line| !line.is_empty()) {
+            let Some((name, key)) = line.split_once('\t') else {
+                return Err("Malformed keystore entry");
+            };
+            keystore.add(name, KeyFile::from_str(key)?)?;
+        }
+        Ok(keystore)
+    }
+}
+
+impl fmt::Display for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, key) in &self.entries {
+            let key = key.to_string();
+            let line = key.lines().find(|line| !line.starts_with('#')).unwrap_or_default();
+            writeln!(f, "{name}\t{line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Keystore {
+    pub fn default_path() -> Result<PathBuf, &'static str> {
+        if let Some(path) = env::var_os(PATH_VAR).filter(|path| !path.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+        let data_dir = match env::var_os("XDG_DATA_HOME").filter(|path| !path.is_empty()) {
+            Some(data_dir) => PathBuf::from(data_dir),
+            None => match env::var_os("HOME") {
+                Some(home) => Path::new(&home).join(".local").join("share"),
+                None => return Err("Cannot find a data directory for the keystore, set STEGANOPNG_KEYSTORE"),
+            },
+        };
+        Ok(data_dir.join("steganopng").join("keystore"))
+    }
+
+    pub fn passphrase(confirm: bool) -> Result<Zeroizing<String>, Box<dyn std::error::Error>> {
+        if let Some(passphrase) = env::var(PASSPHRASE_VAR).ok().filter(|passphrase| !passphrase.is_empty()) {
+            return Ok(Zeroizing::new(passphrase));
+        }
+        let passphrase = secret_input::prompt_hidden("Keystore passphrase: ")?;
+        if passphrase.is_empty() {
+            return Err("The keystore needs a passphrase".into());
+        }
+        if confirm && secret_input::prompt_hidden("Repeat keystore passphrase: ")? != passphrase {
+            return Err("Passphrases do not match".into());
+        }
+        Ok(passphrase)
+    }
+
+    pub fn open(path: &Path, passphrase: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = fs::read(path).map_err(|e| format!("Cannot read keystore {}: {e}", path.display()))?;
+        let envelope = Envelope::try_from(data.as_slice())?;
+        let contents = Zeroizing::new(envelope.open(&[Secret::Passphrase(passphrase.to_string())], &[]).map_err(|_| "Wrong keystore passphrase")?);
+        let contents = std::str::from_utf8(&contents).map_err(|_| "Keystore is corrupted")?;
+        Ok(Self::from_str(contents)?)
+    }
+
+    pub fn save(&self, path: &Path, passphrase: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let contents = Zeroizing::new(self.to_string());
+        let envelope = Envelope::seal(
+            contents.as_bytes(),
+            &[Secret::Passphrase(passphrase.to_string())],
+            Cipher::XChaCha20Poly1305,
+            Padding::Padme,
+            None,
+            None,
+        )?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temporary = path.with_extension("tmp");
+        let _ = fs::remove_file(&temporary);
+        keyfile::write_private(&temporary, &envelope.as_bytes())?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, name: &str, key: KeyFile) -> Result<(), &'static str> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err("Key names cannot be empty or contain whitespace");
+        }
+        if self.get(name).is_some() {
+            return Err("A key with this name already exists");
+        }
+        self.entries.push((name.to_string(), key));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<KeyFile, &'static str> {
+        let Some(index) = self.entries.iter().position(|(entry, _)| entry == name) else {
+            return Err("No key with this name");
+        };
+        Ok(self.entries.remove(index).1)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&KeyFile> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == name)
+            .map(|(_, key)| key)
+    }
+
+    pub fn entries(&self) -> &[(String, KeyFile)] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn testing_keystore() -> Keystore {
+        let mut keystore = Keystore::default();
+        keystore.add("team", KeyFile::generate("symmetric").unwrap()).unwrap();
+        keystore.add("alice", KeyFile::generate("x25519").unwrap()).unwrap();
+        keystore.add("release", KeyFile::generate("ed25519").unwrap()).unwrap();
+        keystore
+    }
+
+    #[test]
+    fn test_keystore_round_trip() {
+        let keystore = testing_keystore();
+        let parsed = Keystore::from_str(&keystore.to_string()).unwrap();
+        assert_eq!(parsed.to_string(), keystore.to_string());
+        assert_eq!(parsed.entries().len(), 3);
+        assert_eq!(parsed.get("alice").unwrap().public_key(), keystore.get("alice").unwrap().public_key());
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut keystore = testing_keystore();
+        assert!(keystore.add("team", KeyFile::generate("symmetric").unwrap()).is_err());
+        assert!(keystore.add("two words", KeyFile::generate("symmetric").unwrap()).is_err());
+        assert!(keystore.remove("team").is_ok());
+        assert!(keystore.get("team").is_none());
+        assert!(keystore.remove("team").is_err());
+    }
+
+    #[test]
+    fn test_save_and_open() {
+        let dir = TempDir::new("keystore");
+        let path = dir.join("nested").join("keystore");
+
+        let keystore = testing_keystore();
+        keystore.save(&path, "keystore passphrase").unwrap();
+        keystore.save(&path, "keystore passphrase").unwrap();
+
+        let opened = Keystore::open(&path, "keystore passphrase").unwrap();
+        assert_eq!(opened.to_string(), keystore.to_string());
+        assert!(Keystore::open(&path, "wrong passphrase").is_err());
+        assert!(!fs::read(&path).unwrap().windows(4).any(|window| window == b"team"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::Serialize;
+use crate::png::Png;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChunkEntry {
+    pub index: usize,
+    pub offset: usize,
+    pub chunk_type: String,
+    pub critical: bool,
+    pub public: bool,
+    pub reserved_bit_valid: bool,
+    pub safe_to_copy: bool,
+    pub length: u32,
+    pub crc: String,
+    pub crc_valid: bool,
+}
+
+impl FromStr for Format {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err("Unknown format, expected text or json"),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+pub fn entries(png: &Png) -> Vec<ChunkEntry> {
+    let mut offset = Png::STANDARD_HEADER.len();
+    png.chunks()
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let chunk_type = chunk.chunk_type();
+            let entry = ChunkEntry {
+                index,
+                offset,
+                chunk_type: chunk_type.to_string(),
+                critical: chunk_type.is_critical(),
+                public: chunk_type.is_public(),
+                reserved_bit_valid: chunk_type.is_reserved_bit_valid(),
+                safe_to_copy: chunk_type.is_safe_to_copy(),
+                length: chunk.length(),
+                crc: format!("{:08x}", chunk.crc),
+                crc_valid: chunk.crc_is_valid(),
+            };
+            offset += 12 + chunk.data().len();
+            entry
+        })
+        .collect()
+}
+
+pub fn render(entries: &[ChunkEntry], format: Format) -> Result<String, serde_json::Error> {
+    match format {
+        Format::Json => serde_json::to_string_pretty(entries),
+        Format::Text => {
+            let mut table = String::from("INDEX  OFFSET      TYPE  PROPERTIES  LENGTH      CRC\n");
+            for entry in entries {
+                let properties: String = [
+                    (entry.critical, 'C', 'a'),
+                    (entry.public, 'P', 'p'),
+                    (entry.reserved_bit_valid, 'R', 'r'),
+                    (entry.safe_to_copy, 'S', 'u'),
+                ]
+                .iter()
+                .map(|&(set, yes, no)| if set { yes } else { no })
+                .collect();
+                table.push_str(&format!(
+                    "{:<5}  {:<10}  {}  {:<10}  {:<10}  {} {}\n",
+                    entry.index,
+                    entry.offset,
+                    entry.chunk_type,
+                    properties,
+                    entry.length,
+                    entry.crc,
+                    if entry.crc_valid { "ok" } else { "BAD" },
+                ));
+            }
+            Ok(table)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::chunk;
+
+    fn testing_png() -> Png {
+        let mut broken = chunk("ruSt", b"message");
+        broken.crc ^= 1;
+        Png::from_chunks(vec![chunk("IHDR", b"header data"), broken, chunk("IEND", b"")])
+    }
+
+    #[test]
+    fn test_entries() {
+        let entries = entries(&testing_png());
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].offset, 8);
+        assert_eq!(entries[1].offset, 8 + 12 + 11);
+        assert_eq!(entries[2].offset, 8 + 12 + 11 + 12 + 7);
+        assert!(entries[0].critical && entries[0].public && !entries[0].safe_to_copy);
+        assert!(!entries[1].critical && entries[1].safe_to_copy);
+        assert!(entries[0].crc_valid);
+        assert!(!entries[1].crc_valid);
+    }
+
+    #[test]
+    fn test_render() {
+        let entries = entries(&testing_png());
+        let table = render(&entries, Format::Text).unwrap();
+        assert!(table.lines().nth(2).unwrap().contains("ruSt  apRS"));
+        assert!(table.lines().nth(2).unwrap().ends_with("BAD"));
+
+        let json: serde_json::Value = serde_json::from_str(&render(&entries, Format::Json).unwrap()).unwrap();
+        assert_eq!(json[1]["chunk_type"], "ruSt");
+        assert_eq!(json[1]["crc_valid"], false);
+        assert_eq!(json[2]["offset"], 50);
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!(Format::from_str("JSON").unwrap(), Format::Json);
+        assert!(Format::from_str("yaml").is_err());
+    }
+}
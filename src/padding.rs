@@ -0,0 +1,149 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Padding {
+    None,
+    Buckets,
+    Padme,
+}
+
+impl TryFrom<u8> for Padding {
+    type Error = &'static str;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Buckets),
+            2 => Ok(Self::Padme),
+            _ => Err("Unsupported padding scheme"),
+        }
+    }
+}
+
+impl FromStr for Padding {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "buckets" | "bucket" => Ok(Self::Buckets),
+            "padme" => Ok(Self::Padme),
+            _ => Err("Unknown padding, expected none, buckets or padme"),
+        }
+    }
+}
+
+impl fmt::Display for Padding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Buckets => write!(f, "buckets"),
+            Self::Padme => write!(f, "padme"),
+        }
+    }
+}
+
+impl Padding {
+    const MIN_BUCKET: usize = 256;
+
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Buckets => 1,
+            Self::Padme => 2,
+        }
+    }
+
+    pub fn padded_len(&self, length: usize) -> usize {
+        match self {
+            Self::None => length,
+            Self::Buckets => (length + 1).next_power_of_two().max(Padding::MIN_BUCKET),
+            Self::Padme => {
+                let length = length + 1;
+                if length < 2 {
+                    return length;
+                }
+                let exponent = length.ilog2();
+                let significant_bits = exponent.ilog2() + 1;
+                let mask = (1 << (exponent - significant_bits)) - 1;
+                (length + mask) & !mask
+            },
+        }
+    }
+
+    pub fn pad(&self, message: &[u8]) -> Vec<u8> {
+        if *self == Self::None {
+            return message.to_vec();
+        }
+        let mut padded = Vec::with_capacity(self.padded_len(message.len()));
+        padded.extend(message);
+        padded.push(0x80);
+        padded.resize(self.padded_len(message.len()), 0);
+        padded
+    }
+
+    pub fn unpad(&self, padded: &[u8]) -> Result<Vec<u8>, &'static str> {
+        if *self == Self::None {
+            return Ok(padded.to_vec());
+        }
+        match padded.iter().rposition(|&byte| byte != 0) {
+            Some(end) if padded[end] == 0x80 => Ok(padded[..end].to_vec()),
+            _ => Err("Invalid padding"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PADDINGS: [Padding; 3] = [Padding::None, Padding::Buckets, Padding::Padme];
+
+    #[test]
+    fn test_padding_ids_and_names_round_trip() {
+        for padding in PADDINGS {
+            assert_eq!(Padding::try_from(padding.id()).unwrap(), padding);
+            assert_eq!(Padding::from_str(&padding.to_string()).unwrap(), padding);
+        }
+        assert!(Padding::try_from(9).is_err());
+        assert!(Padding::from_str("zeros").is_err());
+    }
+
+    #[test]
+    fn test_bucket_sizes() {
+        assert_eq!(Padding::Buckets.padded_len(0), 256);
+        assert_eq!(Padding::Buckets.padded_len(255), 256);
+        assert_eq!(Padding::Buckets.padded_len(256), 512);
+        assert_eq!(Padding::Buckets.padded_len(3000), 4096);
+    }
+
+    #[test]
+    fn test_padme_sizes() {
+        assert_eq!(Padding::Padme.padded_len(9), 10);
+        assert_eq!(Padding::Padme.padded_len(99), 104);
+        assert_eq!(Padding::Padme.padded_len(999), 1024);
+        for length in 0..5000 {
+            let padded = Padding::Padme.padded_len(length);
+            assert!(padded > length && padded - length <= length / 8 + 1);
+        }
+    }
+
+    #[test]
+    fn test_pad_and_unpad() {
+        for padding in PADDINGS {
+            for message in [&b""[..], b"secret message", &[0; 40], &[0x80; 40]] {
+                let padded = padding.pad(message);
+                assert_eq!(padded.len(), padding.padded_len(message.len()));
+                assert_eq!(padding.unpad(&padded).unwrap(), message);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_padding() {
+        assert!(Padding::Padme.unpad(&[1, 2, 3, 0]).is_err());
+        assert!(Padding::Buckets.unpad(&[0; 16]).is_err());
+    }
+}
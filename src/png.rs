@@ -3,7 +3,8 @@ use crate::chunk::Chunk;
 use std::str::FromStr;
 use std::convert::TryFrom;
 use std::fmt;
-use rand::Rng;
+use rand::seq::SliceRandom;
+use sha2::{Digest, Sha256};
 
 #[derive(Debug, Clone)]
 pub struct Png {
@@ -15,19 +16,33 @@ impl TryFrom<&[u8]> for Png {
     type Error = &'static str;
 
     fn try_from(p: &[u8]) -> Result<Self, Self::Error> {
-        let header = &p[0..8];
+        let Some(header) = p.get(0..8) else {
+            return Err("Invalid header");
+        };
         let chunk_list = p[8..].to_vec();
         let mut count: usize = 0;
         let mut chunks: Vec<Chunk> = Vec::new();
 
         loop {
-            let length = u32::from_be_bytes(chunk_list[count..count + 4].try_into().unwrap());
+            let Some(length) = chunk_list.get(count..count + 4) else {
+                return Err("PNG is truncated");
+            };
+            let length = u32::from_be_bytes(length.try_into().unwrap());
             count += 4;
-            let chunk_type = ChunkType::try_from(ChunkType::convert_to_fixed_slice(&chunk_list[count..count + 4]))?;
+            let Some(chunk_type) = chunk_list.get(count..count + 4) else {
+                return Err("PNG is truncated");
+            };
+            let chunk_type = ChunkType::try_from(ChunkType::convert_to_fixed_slice(chunk_type))?;
             count += 4;
-            let data = chunk_list[count..count + length as usize].try_into().unwrap();
+            let Some(data) = chunk_list.get(count..count + length as usize) else {
+                return Err("PNG is truncated");
+            };
+            let data = data.into();
             count += length as usize;
-            let crc = u32::from_be_bytes(chunk_list[count..count + 4].try_into().unwrap());
+            let Some(crc) = chunk_list.get(count..count + 4) else {
+                return Err("PNG is truncated");
+            };
+            let crc = u32::from_be_bytes(crc.try_into().unwrap());
             count += 4;
 
             let chunk = Chunk {
@@ -62,6 +77,37 @@ impl fmt::Display for Png {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Placement {
+    #[default]
+    Random,
+    AfterIhdr,
+    BeforeIend,
+}
+
+impl FromStr for Placement {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "random" => Ok(Self::Random),
+            "after-ihdr" => Ok(Self::AfterIhdr),
+            "before-iend" => Ok(Self::BeforeIend),
+            _ => Err("Unknown placement, expected random, after-ihdr or before-iend"),
+        }
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Random => write!(f, "random"),
+            Self::AfterIhdr => write!(f, "after-ihdr"),
+            Self::BeforeIend => write!(f, "before-iend"),
+        }
+    }
+}
+
 impl Png {
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
@@ -73,13 +119,34 @@ impl Png {
         }
     }
 
-    pub fn append_chunk(&mut self, chunk: Chunk) {
-        let mut rng = rand::thread_rng();
-        let min = 1;
-        let max = self.chunks.len() - 1;
-        self.chunks
-            .insert(rng.gen_range(min..max),
-            chunk);
+    pub fn append_chunk(&mut self, chunk: Chunk) -> Result<(), &'static str> {
+        if self.chunks.len() < 2 {
+            return Err("PNG needs at least an IHDR and an IEND chunk");
+        }
+        let is_idat = |index: usize| self.chunks[index].chunk_type().to_string() == "IDAT";
+        let gaps: Vec<usize> = (1..self.chunks.len())
+            .filter(|&index| !(is_idat(index - 1) && is_idat(index)))
+            .collect();
+        let Some(&index) = gaps.choose(&mut rand::thread_rng()) else {
+            return Err("PNG has no place for a chunk outside its IDAT run");
+        };
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
+    pub fn insert_chunk(&mut self, chunk: Chunk, placement: Placement) -> Result<(), &'static str> {
+        match placement {
+            Placement::Random => return self.append_chunk(chunk),
+            Placement::AfterIhdr => self.chunks.insert(1.min(self.chunks.len()), chunk),
+            Placement::BeforeIend => {
+                let index = self.chunks
+                    .iter()
+                    .position(|chunk| chunk.chunk_type().to_string() == "IEND")
+                    .unwrap_or(self.chunks.len());
+                self.chunks.insert(index, chunk);
+            },
+        }
+        Ok(())
     }
 
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk, Box<dyn std::error::Error>> {
@@ -94,6 +161,14 @@ impl Png {
         Ok(self.chunks.remove(index))
     }
 
+    pub fn replace_chunk(&mut self, chunk: Chunk) -> Result<Chunk, Box<dyn std::error::Error>> {
+        let Some(index) = self.chunks.iter().position(|existing| existing.chunk_type == chunk.chunk_type) else {
+            return Err("Chunk not found".into());
+        };
+
+        Ok(std::mem::replace(&mut self.chunks[index], chunk))
+    }
+
     pub fn header(&self) -> [u8; 8] {
         self.header
     }
@@ -108,6 +183,26 @@ impl Png {
             .find(|chunk| chunk.chunk_type == ChunkType::from_str(chunk_type).unwrap())
     }
 
+    pub fn carrier_binding(&self, chunk_type: &ChunkType) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        self.chunks
+            .iter()
+            .filter(|chunk| matches!(chunk.chunk_type().to_string().as_str(), "IHDR" | "IDAT"))
+            .for_each(|chunk| {
+                hasher.update(chunk.length().to_be_bytes());
+                hasher.update(chunk.chunk_type().bytes());
+                hasher.update(chunk.data());
+                hasher.update(chunk.crc.to_be_bytes());
+            });
+
+        b"steganopng carrier"
+            .iter()
+            .chain(chunk_type.bytes().iter())
+            .chain(hasher.finalize().iter())
+            .copied()
+            .collect()
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let chunk_bytes: Vec<u8> = self.chunks.clone()
             .into_iter()
@@ -127,6 +222,7 @@ impl Png {
 mod tests {
     use super::*;
 
+    #[allow(clippy::vec_init_then_push)]
     fn testing_chunks() -> Vec<Chunk> {
         let mut chunks = Vec::new();
 
@@ -215,6 +311,15 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_truncated_png() {
+        let bytes = Png::from_chunks(testing_chunks()).as_bytes();
+
+        assert!(Png::try_from(&bytes[..4]).is_err());
+        assert!(Png::try_from(&bytes[..8]).is_err());
+        assert!(Png::try_from(&bytes[..bytes.len() - 2]).is_err());
+    }
+
 
     #[test]
     fn test_list_chunks() {
@@ -235,7 +340,7 @@ mod tests {
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
         let chunk = png.chunk_by_type("TeSt").unwrap();
         assert_eq!(&chunk.chunk_type().to_string(), "TeSt");
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
@@ -244,18 +349,98 @@ mod tests {
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
         png.remove_chunk("TeSt").unwrap();
         let chunk = png.chunk_by_type("TeSt");
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_insert_chunk_placement() {
+        let mut png = testing_png();
+        png.insert_chunk(chunk_from_strings("TeSt", "After").unwrap(), Placement::AfterIhdr).unwrap();
+        assert_eq!(png.chunks()[1].data_as_string().unwrap(), "After");
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_chunk(chunk_from_strings("TeSt", "Before").unwrap(), Placement::BeforeIend).unwrap();
+        let count = png.chunks().len();
+        assert_eq!(png.chunks()[count - 2].data_as_string().unwrap(), "Before");
+        assert_eq!(png.chunks()[count - 1].chunk_type().to_string(), "IEND");
+
+        for placement in [Placement::Random, Placement::AfterIhdr, Placement::BeforeIend] {
+            assert_eq!(Placement::from_str(&placement.to_string()).unwrap(), placement);
+        }
+        assert!(Placement::from_str("middle").is_err());
+    }
+
+    #[test]
+    fn test_random_placement_keeps_idat_run() {
+        let types = ["IHDR", "gAMA", "IDAT", "IDAT", "IDAT", "IEND"];
+        for _ in 0..200 {
+            let mut png = Png::from_chunks(types.iter().map(|chunk_type| chunk_from_strings(chunk_type, "data").unwrap()).collect());
+            png.insert_chunk(chunk_from_strings("ruSt", "Message").unwrap(), Placement::Random).unwrap();
+            let actual: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+            assert_eq!(actual.first().unwrap(), "IHDR");
+            assert_eq!(actual.last().unwrap(), "IEND");
+            assert!(actual.join(",").contains("IDAT,IDAT,IDAT"), "{actual:?}");
+        }
+
+        let mut png = Png::from_chunks(vec![chunk_from_strings("IHDR", "data").unwrap(), chunk_from_strings("IEND", "").unwrap()]);
+        png.insert_chunk(chunk_from_strings("ruSt", "Message").unwrap(), Placement::Random).unwrap();
+        assert_eq!(png.chunks()[1].chunk_type().to_string(), "ruSt");
+
+        let mut png = Png::from_chunks(vec![chunk_from_strings("IHDR", "data").unwrap()]);
+        assert!(png.insert_chunk(chunk_from_strings("ruSt", "Message").unwrap(), Placement::Random).is_err());
+
+        let mut png = Png::from_chunks(vec![chunk_from_strings("IDAT", "data").unwrap(), chunk_from_strings("IDAT", "data").unwrap()]);
+        assert!(png.insert_chunk(chunk_from_strings("ruSt", "Message").unwrap(), Placement::Random).is_err());
+        assert_eq!(png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        let before = png.as_bytes();
+        let old = png.replace_chunk(chunk_from_strings("miDl", "Replaced").unwrap()).unwrap();
+        assert_eq!(&old.data_as_string().unwrap(), "I am another chunk");
+        assert_eq!(png.chunks()[1].data_as_string().unwrap(), "Replaced");
+        assert_eq!(png.as_bytes()[..8 + 32], before[..8 + 32]);
+        assert!(png.replace_chunk(chunk_from_strings("NoNe", "Missing").unwrap()).is_err());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
         assert!(png.is_ok());
     }
 
+    #[test]
+    fn test_carrier_binding() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let binding = png.carrier_binding(&chunk_type);
+
+        png.append_chunk(chunk_from_strings("ruSt", "Message").unwrap()).unwrap();
+        assert_eq!(png.carrier_binding(&chunk_type), binding);
+        assert_ne!(png.carrier_binding(&ChunkType::from_str("ruSx").unwrap()), binding);
+
+        let index = png.chunks.iter().position(|chunk| chunk.chunk_type().to_string() == "IDAT").unwrap();
+        let mut data = png.chunks[index].data().to_vec();
+        data[0] ^= 1;
+        png.chunks[index] = Chunk::new(ChunkType::from_str("IDAT").unwrap(), data);
+        assert_ne!(png.carrier_binding(&chunk_type), binding);
+    }
+
+    #[test]
+    fn test_carrier_binding_with_bad_crc() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let binding = png.carrier_binding(&chunk_type);
+
+        png.chunks[0].crc ^= 1;
+        assert_ne!(png.carrier_binding(&chunk_type), binding);
+    }
+
     #[test]
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
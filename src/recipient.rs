@@ -0,0 +1,150 @@
+use std::fmt;
+use std::str::FromStr;
+use deoxys::aead::OsRng;
+use generic_array::GenericArray;
+use generic_array::typenum::U32;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipient(pub PublicKey);
+
+#[derive(Clone)]
+pub struct Identity(pub StaticSecret);
+
+impl FromStr for Recipient {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(key) = s.trim().strip_prefix(Recipient::PREFIX) else {
+            return Err("Recipients start with spng-pub-");
+        };
+        let key: [u8; 32] = hex::decode(key)
+            .map_err(|_| "Recipient is not valid hex")?
+            .try_into()
+            .map_err(|_| "Recipient must be 32 bytes")?;
+        Ok(Self(PublicKey::from(key)))
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", Recipient::PREFIX, hex::encode(self.0.as_bytes()))
+    }
+}
+
+impl Recipient {
+    pub const PREFIX: &'static str = "spng-pub-";
+
+    pub fn wrapping_key(&self) -> Result<([u8; 32], GenericArray<u8, U32>), &'static str> {
+        let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
+        let ephemeral_public = PublicKey::from(&ephemeral_secret);
+        let shared_secret = ephemeral_secret.diffie_hellman(&self.0);
+        if !shared_secret.was_contributory() {
+            return Err("Recipient is a low-order point");
+        }
+        let key = derive_wrapping_key(shared_secret.as_bytes(), ephemeral_public.as_bytes(), self.0.as_bytes());
+        Ok((ephemeral_public.to_bytes(), key))
+    }
+}
+
+impl FromStr for Identity {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(line) = s.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with('#')) else {
+            return Err("No identity found");
+        };
+        let Some(key) = line.strip_prefix(Identity::PREFIX) else {
+            return Err("Identities start with SPNG-SECRET-");
+        };
+        let key: [u8; 32] = hex::decode(key)
+            .map_err(|_| "Identity is not valid hex")?
+            .try_into()
+            .map_err(|_| "Identity must be 32 bytes")?;
+        Ok(Self(StaticSecret::from(key)))
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# public key: {}", self.recipient())?;
+        write!(f, "{}{}", Identity::PREFIX, hex::encode(self.0.as_bytes()))
+    }
+}
+
+impl Identity {
+    pub const PREFIX: &'static str = "SPNG-SECRET-";
+
+    pub fn generate() -> Self {
+        Self(StaticSecret::random_from_rng(OsRng))
+    }
+
+    pub fn recipient(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0))
+    }
+
+    pub fn wrapping_key(&self, ephemeral_public: &[u8; 32]) -> Option<GenericArray<u8, U32>> {
+        let shared_secret = self.0.diffie_hellman(&PublicKey::from(*ephemeral_public));
+        if !shared_secret.was_contributory() {
+            return None;
+        }
+        Some(derive_wrapping_key(shared_secret.as_bytes(), ephemeral_public, self.recipient().0.as_bytes()))
+    }
+}
+
+fn derive_wrapping_key(shared_secret: &[u8; 32], ephemeral_public: &[u8; 32], recipient: &[u8; 32]) -> GenericArray<u8, U32> {
+    let salt: Vec<u8> = ephemeral_public
+        .iter()
+        .chain(recipient.iter())
+        .copied()
+        .collect();
+    let mut key = GenericArray::default();
+    Hkdf::<Sha256>::new(Some(&salt), shared_secret)
+        .expand(b"steganopng X25519", &mut key)
+        .unwrap();
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recipient_round_trip() {
+        let recipient = Identity::generate().recipient();
+        let actual = Recipient::from_str(&recipient.to_string()).unwrap();
+        assert_eq!(actual, recipient);
+        assert!(recipient.to_string().starts_with("spng-pub-"));
+    }
+
+    #[test]
+    fn test_identity_round_trip() {
+        let identity = Identity::generate();
+        let actual = Identity::from_str(&identity.to_string()).unwrap();
+        assert_eq!(actual.recipient(), identity.recipient());
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        assert!(Recipient::from_str("spng-pub-abcd").is_err());
+        assert!(Recipient::from_str(&"00".repeat(32)).is_err());
+        assert!(Identity::from_str("# only a comment").is_err());
+        assert!(Identity::from_str("SPNG-SECRET-zz").is_err());
+    }
+
+    #[test]
+    fn test_wrapping_keys_agree() {
+        let identity = Identity::generate();
+        let (ephemeral_public, key) = identity.recipient().wrapping_key().unwrap();
+        assert_eq!(identity.wrapping_key(&ephemeral_public).unwrap(), key);
+        assert_ne!(Identity::generate().wrapping_key(&ephemeral_public).unwrap(), key);
+    }
+
+    #[test]
+    fn test_low_order_recipient() {
+        let recipient = Recipient(PublicKey::from([0; 32]));
+        assert!(recipient.wrapping_key().is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+use crate::commands::SecretOptions;
+use crate::envelope::Secret;
+use crate::keyfile::KeyFile;
+use crate::keystore::Keystore;
+use generic_array::GenericArray;
+use generic_array::typenum::U32;
+use zeroize::Zeroizing;
+
+pub const PASSPHRASE_VAR: &str = "STEGANOPNG_PASSPHRASE";
+pub const KEY_VAR: &str = "STEGANOPNG_KEY";
+#[cfg(unix)]
+const TERMINAL: &str = "/dev/tty";
+
+impl SecretOptions {
+    pub fn load(&self) -> Result<Vec<Secret>, Box<dyn std::error::Error>> {
+        let mut secrets = self.key_files
+            .iter()
+            .chain(&self.identities)
+            .map(|path| Ok(KeyFile::read(path)?.secret()?))
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+        if !self.key_names.is_empty() {
+            let keystore = Keystore::open(&Keystore::default_path()?, &Keystore::passphrase(false)?)?;
+            for name in &self.key_names {
+                let Some(key) = keystore.get(name) else {
+                    return Err(format!("No key named {name} in the keystore").into());
+                };
+                secrets.push(key.secret()?);
+            }
+        }
+        if let Some(key) = self.key()? {
+            secrets.push(Secret::Key(key));
+        }
+        if let Some(passphrase) = passphrase(self.passphrase_file.as_deref())? {
+            secrets.push(Secret::Passphrase(passphrase.to_string()));
+        }
+        Ok(secrets)
+    }
+
+    pub fn key(&self) -> Result<Option<GenericArray<u8, U32>>, Box<dyn std::error::Error>> {
+        match &self.key {
+            Some(key) => Ok(Some(parse_key(key)?)),
+            None => key(self.key_fd.as_deref()),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn is_interactive() -> bool {
+    fs::OpenOptions::new().read(true).write(true).open(TERMINAL).is_ok()
+}
+
+#[cfg(not(unix))]
+pub fn is_interactive() -> bool {
+    use std::io::IsTerminal;
+    io::stdin().is_terminal()
+}
+
+pub fn prompt_hidden(message: &str) -> Result<Zeroizing<String>, Box<dyn std::error::Error>> {
+    if !is_interactive() {
+        return Err(not_interactive(message).into());
+    }
+    eprint!("{message}");
+    Ok(Zeroizing::new(rpassword::read_password()?))
+}
+
+pub fn prompt(message: &str) -> Result<String, Box<dyn std::error::Error>> {
+    if !is_interactive() {
+        return Err(not_interactive(message).into());
+    }
+    eprint!("{message}");
+    let mut input = String::new();
+    terminal()?.read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+#[cfg(unix)]
+fn terminal() -> io::Result<Box<dyn BufRead>> {
+    Ok(Box::new(io::BufReader::new(fs::File::open(TERMINAL)?)))
+}
+
+#[cfg(not(unix))]
+fn terminal() -> io::Result<Box<dyn BufRead>> {
+    Ok(Box::new(io::stdin().lock()))
+}
+
+pub fn confirm(message: &str, assume_yes: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    if assume_yes || !is_interactive() {
+        return Ok(true);
+    }
+    match prompt(message)?.as_str() {
+        "Y" | "y" | "" => Ok(true),
+        "N" | "n" => Ok(false),
+        _ => Err("Invalid input".into()),
+    }
+}
+
+fn not_interactive(message: &str) -> String {
+    let wanted = message.split(['(', ':']).next().unwrap_or_default().trim().trim_start_matches("Enter ");
+    format!("No {} given and there is no terminal to ask on, supply it with a flag or environment variable", wanted.to_lowercase())
+}
+
+pub fn parse_key(input: &str) -> Result<GenericArray<u8, U32>, Box<dyn std::error::Error>> {
+    let input = input.trim();
+    if input.starts_with(KeyFile::SYMMETRIC_PREFIX) || input.starts_with('#') {
+        return match KeyFile::from_str(input)? {
+            KeyFile::Symmetric(key) => Ok(key),
+            _ => Err("Expected a symmetric key".into()),
+        };
+    }
+
+    let key = Zeroizing::new(hex::decode(input).map_err(|_| "Key is not valid hex")?);
+    if key.len() != 32 {
+        return Err("Key must be 32 bytes".into());
+    }
+    Ok(*GenericArray::from_slice(&key))
+}
+
+pub fn passphrase(passphrase_file: Option<&str>) -> Result<Option<Zeroizing<String>>, Box<dyn std::error::Error>> {
+    if let Some(path) = passphrase_file {
+        let contents = Zeroizing::new(fs::read_to_string(path).map_err(|e| format!("Cannot read passphrase file {path}: {e}"))?);
+        let Some(line) = contents.lines().next().filter(|line| !line.is_empty()) else {
+            return Err(format!("Passphrase file {path} is empty").into());
+        };
+        return Ok(Some(Zeroizing::new(line.to_string())));
+    }
+
+    Ok(env::var(PASSPHRASE_VAR).ok().filter(|passphrase| !passphrase.is_empty()).map(Zeroizing::new))
+}
+
+pub fn key(key_fd: Option<&str>) -> Result<Option<GenericArray<u8, U32>>, Box<dyn std::error::Error>> {
+    if let Some(fd) = key_fd {
+        let fd: u32 = fd.parse().map_err(|_| "--key-fd expects a file descriptor number")?;
+        let contents = Zeroizing::new(fs::read_to_string(format!("/dev/fd/{fd}")).map_err(|e| format!("Cannot read key from descriptor {fd}: {e}"))?);
+        return Ok(Some(parse_key(&contents)?));
+    }
+
+    match env::var(KEY_VAR) {
+        Ok(key) if !key.is_empty() => Ok(Some(parse_key(&Zeroizing::new(key))?)),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_parse_key() {
+        let hex_key = "2a".repeat(32);
+        assert_eq!(parse_key(&hex_key).unwrap().as_slice(), &[42; 32]);
+        assert_eq!(parse_key(&format!("SPNG-KEY-{hex_key}\n")).unwrap().as_slice(), &[42; 32]);
+        assert!(parse_key("2a2a").is_err());
+        assert!(parse_key("not a key").is_err());
+    }
+
+    #[test]
+    fn test_passphrase_file() {
+        let dir = TempDir::new("secret");
+        let path = dir.write("passphrase", "correct horse battery staple\n");
+        let passphrase = passphrase(Some(&path)).unwrap().unwrap();
+        assert_eq!(passphrase.as_str(), "correct horse battery staple");
+    }
+
+    #[test]
+    fn test_empty_passphrase_file() {
+        let dir = TempDir::new("secret");
+        let path = dir.write("empty", "");
+        assert!(passphrase(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn test_load_sources() {
+        let dir = TempDir::new("secret");
+        let passphrase_path = dir.write("sources-passphrase", "hunter2\n");
+        let sources = SecretOptions {
+            passphrase_file: Some(passphrase_path),
+            ..SecretOptions::default()
+        };
+        let secrets = sources.load().unwrap();
+        assert!(secrets.iter().any(|secret| matches!(secret, Secret::Passphrase(passphrase) if passphrase == "hunter2")));
+
+        let sources = SecretOptions {
+            identities: vec![String::from("/nonexistent/steganopng.key")],
+            ..SecretOptions::default()
+        };
+        assert!(sources.load().is_err());
+    }
+
+    #[test]
+    fn test_key_flag() {
+        let sources = SecretOptions {
+            key: Some("2a".repeat(32)),
+            ..SecretOptions::default()
+        };
+        assert_eq!(sources.key().unwrap().unwrap().as_slice(), &[42; 32]);
+        assert!(matches!(sources.load().unwrap()[0], Secret::Key(_)));
+    }
+
+    #[test]
+    fn test_key_fd() {
+        let dir = TempDir::new("secret");
+        let path = dir.write("key", "2a".repeat(32));
+        let file = fs::File::open(&path).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            let fd = file.as_raw_fd().to_string();
+            assert_eq!(key(Some(&fd)).unwrap().unwrap().as_slice(), &[42; 32]);
+        }
+
+        drop(file);
+        assert!(key(Some("not a number")).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use rand::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Split {
+    pub threshold: u8,
+    pub shares: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    pub id: [u8; 8],
+    pub threshold: u8,
+    pub index: u8,
+    pub data: Vec<u8>,
+}
+
+impl FromStr for Split {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((threshold, shares)) = s.split_once("-of-") else {
+            return Err("Expected a split like 3-of-5");
+        };
+        let threshold: u8 = threshold.parse().map_err(|_| "Invalid share threshold")?;
+        let shares: u8 = shares.parse().map_err(|_| "Invalid share count")?;
+        if threshold < 2 || threshold > shares {
+            return Err("Share threshold must be at least 2 and at most the share count");
+        }
+        Ok(Self { threshold, shares })
+    }
+}
+
+impl fmt::Display for Split {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-of-{}", self.threshold, self.shares)
+    }
+}
+
+impl TryFrom<&[u8]> for Share {
+    type Error = &'static str;
+
+    fn try_from(s: &[u8]) -> Result<Self, Self::Error> {
+        if !Share::matches(s) || s.len() < Share::HEADER_LEN {
+            return Err("Chunk does not hold a share");
+        }
+        if s[4] != Share::VERSION {
+            return Err("Unsupported share version");
+        }
+        let share = Share {
+            id: s[5..13].try_into().unwrap(),
+            threshold: s[13],
+            index: s[14],
+            data: s[Share::HEADER_LEN..].to_vec(),
+        };
+        if share.index == 0 || share.threshold < 2 {
+            return Err("Malformed share");
+        }
+        Ok(share)
+    }
+}
+
+impl Share {
+    pub const MAGIC: &'static [u8; 4] = b"SPSS";
+    pub const VERSION: u8 = 1;
+    const HEADER_LEN: usize = 15;
+
+    pub fn matches(data: &[u8]) -> bool {
+        data.starts_with(Share::MAGIC)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        Share::MAGIC
+            .iter()
+            .chain([Share::VERSION].iter())
+            .chain(self.id.iter())
+            .chain([self.threshold, self.index].iter())
+            .chain(self.data.iter())
+            .copied()
+            .collect()
+    }
+}
+
+pub fn split(secret: &[u8], split: Split) -> Vec<Share> {
+    let mut rng = rand::thread_rng();
+    let id: [u8; 8] = rng.gen();
+    let mut shares: Vec<Share> = (1..=split.shares)
+        .map(|index| Share { id, threshold: split.threshold, index, data: Vec::with_capacity(secret.len()) })
+        .collect();
+
+    for &byte in secret {
+        let coefficients: Vec<u8> = std::iter::once(byte)
+            .chain((1..split.threshold).map(|_| rng.gen()))
+            .collect();
+        for share in shares.iter_mut() {
+            let value = coefficients
+                .iter()
+                .rev()
+                .fold(0, |acc, &coefficient| gf_mul(acc, share.index) ^ coefficient);
+            share.data.push(value);
+        }
+    }
+
+    shares
+}
+
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>, &'static str> {
+    let Some(first) = shares.first() else {
+        return Err("No shares supplied");
+    };
+
+    let mut unique: Vec<&Share> = Vec::new();
+    for share in shares {
+        if share.id != first.id || share.threshold != first.threshold || share.data.len() != first.data.len() {
+            return Err("Shares belong to different secrets");
+        }
+        if !unique.iter().any(|other| other.index == share.index) {
+            unique.push(share);
+        }
+    }
+    if unique.len() < first.threshold as usize {
+        return Err("Not enough shares to reconstruct the secret");
+    }
+    let unique = &unique[..first.threshold as usize];
+
+    let weights: Vec<u8> = unique
+        .iter()
+        .map(|share| {
+            unique
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1, |acc, other| gf_mul(acc, gf_div(other.index, other.index ^ share.index)))
+        })
+        .collect();
+
+    Ok((0..first.data.len())
+        .map(|i| {
+            unique
+                .iter()
+                .zip(&weights)
+                .fold(0, |acc, (share, &weight)| acc ^ gf_mul(share.data[i], weight))
+        })
+        .collect())
+}
+
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    let inverse = (0..253).fold(b, |acc, _| gf_mul(acc, b));
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_3_of_5() -> Split {
+        Split::from_str("3-of-5").unwrap()
+    }
+
+    #[test]
+    fn test_split_from_str() {
+        assert_eq!(split_3_of_5(), Split { threshold: 3, shares: 5 });
+        assert_eq!(split_3_of_5().to_string(), "3-of-5");
+        assert!(Split::from_str("1-of-5").is_err());
+        assert!(Split::from_str("4-of-3").is_err());
+        assert!(Split::from_str("three of five").is_err());
+    }
+
+    #[test]
+    fn test_gf_arithmetic() {
+        assert_eq!(gf_mul(0x53, 0xca), 0x01);
+        assert_eq!(gf_div(1, 0x53), 0xca);
+        for b in 1..=255 {
+            assert_eq!(gf_mul(gf_div(7, b), b), 7);
+        }
+    }
+
+    #[test]
+    fn test_any_threshold_of_shares_combine() {
+        let shares = split(b"launch codes", split_3_of_5());
+        assert_eq!(shares.len(), 5);
+        assert_eq!(combine(&shares).unwrap(), b"launch codes");
+        assert_eq!(combine(&shares[2..]).unwrap(), b"launch codes");
+        assert_eq!(combine(&[shares[4].clone(), shares[0].clone(), shares[2].clone()]).unwrap(), b"launch codes");
+    }
+
+    #[test]
+    fn test_too_few_shares() {
+        let shares = split(b"launch codes", split_3_of_5());
+        assert!(combine(&shares[..2]).is_err());
+        assert!(combine(&[shares[0].clone(), shares[0].clone(), shares[1].clone()]).is_err());
+        assert!(combine(&[]).is_err());
+    }
+
+    #[test]
+    fn test_shares_from_different_secrets() {
+        let mut shares = split(b"launch codes", split_3_of_5());
+        shares[0] = split(b"launch codes", split_3_of_5()).remove(0);
+        assert!(combine(&shares[..3]).is_err());
+    }
+
+    #[test]
+    fn test_share_round_trip() {
+        let share = split(b"launch codes", split_3_of_5()).remove(1);
+        let bytes = share.as_bytes();
+        assert!(Share::matches(&bytes));
+        assert_eq!(Share::try_from(bytes.as_slice()).unwrap(), share);
+        assert!(Share::try_from(&b"SPNG"[..]).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+use std::str::FromStr;
+use deoxys::aead::OsRng;
+use ed25519_dalek::{Signature, Signer as _, SigningKey, Verifier as _, VerifyingKey};
+
+#[derive(Clone)]
+pub struct Signer(pub SigningKey);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verifier(pub VerifyingKey);
+
+impl FromStr for Signer {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(line) = s.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with('#')) else {
+            return Err("No signing key found");
+        };
+        let Some(key) = line.strip_prefix(Signer::PREFIX) else {
+            return Err("Signing keys start with SPNG-SIGNING-");
+        };
+        let key: [u8; 32] = hex::decode(key)
+            .map_err(|_| "Signing key is not valid hex")?
+            .try_into()
+            .map_err(|_| "Signing key must be 32 bytes")?;
+        Ok(Self(SigningKey::from_bytes(&key)))
+    }
+}
+
+impl fmt::Display for Signer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# public key: {}", self.verifier())?;
+        write!(f, "{}{}", Signer::PREFIX, hex::encode(self.0.to_bytes()))
+    }
+}
+
+impl Signer {
+    pub const PREFIX: &'static str = "SPNG-SIGNING-";
+
+    pub fn generate() -> Self {
+        Self(SigningKey::generate(&mut OsRng))
+    }
+
+    pub fn verifier(&self) -> Verifier {
+        Verifier(self.0.verifying_key())
+    }
+
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        self.0.sign(message).to_bytes()
+    }
+}
+
+impl FromStr for Verifier {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(key) = s.trim().strip_prefix(Verifier::PREFIX) else {
+            return Err("Verifying keys start with spng-sig-");
+        };
+        let key: [u8; 32] = hex::decode(key)
+            .map_err(|_| "Verifying key is not valid hex")?
+            .try_into()
+            .map_err(|_| "Verifying key must be 32 bytes")?;
+        Ok(Self(VerifyingKey::from_bytes(&key).map_err(|_| "Invalid verifying key")?))
+    }
+}
+
+impl fmt::Display for Verifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", Verifier::PREFIX, hex::encode(self.0.as_bytes()))
+    }
+}
+
+impl Verifier {
+    pub const PREFIX: &'static str = "spng-sig-";
+
+    pub fn verify(&self, message: &[u8], signature: &[u8; 64]) -> bool {
+        self.0.verify(message, &Signature::from_bytes(signature)).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signer_round_trip() {
+        let signer = Signer::generate();
+        let actual = Signer::from_str(&signer.to_string()).unwrap();
+        assert_eq!(actual.verifier(), signer.verifier());
+    }
+
+    #[test]
+    fn test_verifier_round_trip() {
+        let verifier = Signer::generate().verifier();
+        let actual = Verifier::from_str(&verifier.to_string()).unwrap();
+        assert_eq!(actual, verifier);
+        assert!(verifier.to_string().starts_with("spng-sig-"));
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = Signer::generate();
+        let signature = signer.sign(b"message");
+        assert!(signer.verifier().verify(b"message", &signature));
+        assert!(!signer.verifier().verify(b"massage", &signature));
+        assert!(!Signer::generate().verifier().verify(b"message", &signature));
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        assert!(Signer::from_str("SPNG-SECRET-00").is_err());
+        assert!(Verifier::from_str("spng-pub-00").is_err());
+    }
+}